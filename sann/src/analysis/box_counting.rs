@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use file_io::points::read_points_csv;
+use graph::cyclic_graph_from_coords;
+use graph::types::Graph;
+use linalg_helpers::{linear_fit, log_spaced};
+
+// Same range as box_counting/box.py (np.logspace(-2.0, 0, ...)), but with fewer epsilons so it's cheap enough to record.
+pub const DEFAULT_LOW_EXP: f64 = -2.0;
+pub const DEFAULT_HIGH_EXP: f64 = 0.0;
+pub const DEFAULT_NUM_EPSILONS: usize = 30;
+
+#[derive(Clone, Debug)]
+pub struct BoxCountingFit {
+    pub dimension: f64,
+    pub intercept: f64,
+    pub r_squared: f64,
+    pub epsilons: Vec<f64>,
+    pub counts: Vec<usize>,
+}
+
+impl BoxCountingFit {
+    // The points of the log-log plot: (log(1/epsilon), log(N(epsilon)))
+    pub fn log_log_points(&self) -> Vec<(f64, f64)> {
+        self.epsilons
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(e, c)| ((1.0 / e).ln(), (*c as f64).ln()))
+            .collect()
+    }
+}
+
+pub fn default_epsilons() -> Vec<f64> {
+    log_spaced(DEFAULT_LOW_EXP, DEFAULT_HIGH_EXP, DEFAULT_NUM_EPSILONS)
+}
+
+fn bounding_box_origin(g: &Graph) -> (f64, f64) {
+    g.nodes
+        .iter()
+        .fold((f64::INFINITY, f64::INFINITY), |(min_x, min_y), n| (min_x.min(n.x), min_y.min(n.y)))
+}
+
+fn cell_of(x: f64, y: f64, (origin_x, origin_y): (f64, f64), epsilon: f64) -> (i64, i64) {
+    (((x - origin_x) / epsilon).floor() as i64, ((y - origin_y) / epsilon).floor() as i64)
+}
+
+/* Walks the grid cells crossed by the segment (x1, y1) -> (x2, y2), Amanatides & Woo style,
+   so a long edge marks every box it goes through instead of just the boxes of its end points */
+fn mark_cells_crossed(x1: f64, y1: f64, x2: f64, y2: f64, origin: (f64, f64), epsilon: f64, cells: &mut HashSet<(i64, i64)>) {
+    let (mut i, mut j) = cell_of(x1, y1, origin, epsilon);
+    let last = cell_of(x2, y2, origin, epsilon);
+    cells.insert((i, j));
+
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let (step_i, step_j) = (if dx > 0.0 { 1 } else { -1 }, if dy > 0.0 { 1 } else { -1 });
+    let boundary_t = |cell: i64, from: f64, origin: f64, d: f64| {
+        if d > 0.0 {
+            ((cell + 1) as f64 * epsilon + origin - from) / d
+        } else if d < 0.0 {
+            (cell as f64 * epsilon + origin - from) / d
+        } else {
+            f64::INFINITY
+        }
+    };
+    let mut t_max_x = boundary_t(i, x1, origin.0, dx);
+    let mut t_max_y = boundary_t(j, y1, origin.1, dy);
+    let t_delta_x = if dx != 0.0 { epsilon / dx.abs() } else { f64::INFINITY };
+    let t_delta_y = if dy != 0.0 { epsilon / dy.abs() } else { f64::INFINITY };
+
+    while (i, j) != last && t_max_x.min(t_max_y) <= 1.0 {
+        if t_max_x < t_max_y {
+            i += step_i;
+            t_max_x += t_delta_x;
+        } else {
+            j += step_j;
+            t_max_y += t_delta_y;
+        }
+        cells.insert((i, j));
+    }
+    cells.insert(last);
+}
+
+// Number of epsilon-sized boxes crossed by the edges of g. The grid starts at the bounding box's lower left corner, like box.py
+pub fn count_boxes(g: &Graph, epsilon: f64) -> usize {
+    let origin = bounding_box_origin(g);
+    let mut cells = HashSet::new();
+    for n in &g.nodes {
+        let next = n.next(g);
+        mark_cells_crossed(n.x, n.y, next.x, next.y, origin, epsilon, &mut cells);
+    }
+    cells.len()
+}
+
+// Fits log(N(epsilon)) = D * log(1/epsilon) + c. D is the box counting dimension, r_squared tells how straight the log-log plot is
+pub fn fractal_dimension(g: &Graph, epsilons: &[f64]) -> BoxCountingFit {
+    let counts: Vec<usize> = epsilons.iter().map(|e| count_boxes(g, *e)).collect();
+    let log_inv_epsilons: Vec<f64> = epsilons.iter().map(|e| (1.0 / e).ln()).collect();
+    let log_counts: Vec<f64> = counts.iter().map(|c| (*c as f64).ln()).collect();
+    let (dimension, intercept, r_squared) = linear_fit(&log_inv_epsilons, &log_counts);
+    BoxCountingFit {
+        dimension,
+        intercept,
+        r_squared,
+        epsilons: epsilons.to_vec(),
+        counts,
+    }
+}
+
+pub fn write_log_log_csv(fit: &BoxCountingFit, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(file_path)?;
+    writeln!(file, "epsilon,count,log_inv_epsilon,log_count")?;
+    for ((e, c), (log_inv_e, log_c)) in fit.epsilons.iter().zip(fit.counts.iter()).zip(fit.log_log_points()) {
+        writeln!(file, "{},{},{},{}", e, c, log_inv_e, log_c)?;
+    }
+    Ok(())
+}
+
+// Standalone version of box.py: reads a closed contour from a CSV, fits its dimension and writes the log-log table to output_path
+pub fn analyse_csv(input_path: &str, output_path: &str, epsilons: &[f64]) -> Result<BoxCountingFit, Box<dyn Error>> {
+    let points = read_points_csv(input_path)?;
+    if points.len() < 3 {
+        return Err(From::from(format!("{}: a contour needs at least 3 points, found {}", input_path, points.len())));
+    }
+    let fit = fractal_dimension(&cyclic_graph_from_coords(&points), epsilons);
+    write_log_log_csv(&fit, output_path)?;
+    Ok(fit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_graph;
+
+    #[test]
+    fn edges_are_counted_not_just_vertices() {
+        // A 1x1 square with only 4 vertices still crosses every box along its sides
+        let square = cyclic_graph_from_coords(&vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert!(count_boxes(&square, 0.1) >= 40);
+    }
+
+    #[test]
+    fn smooth_curve_has_dimension_close_to_one() {
+        let circle = circular_graph(0.0, 0.0, 1.0, 2000);
+        let fit = fractal_dimension(&circle, &log_spaced(-2.0, -0.5, 15));
+        assert!(fit.dimension > 0.9 && fit.dimension < 1.1);
+        assert!(fit.r_squared > 0.99);
+    }
+}
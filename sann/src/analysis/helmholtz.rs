@@ -0,0 +1,184 @@
+/* Native port of helmholtz/helmholtz_sars.py ("2D PacMan melter").
+   The gray matter of a ThickSurface is rasterized (see analysis::raster), the white matter is held at a high temperature,
+   the outside at a low one, and an explicit (forward Euler) heat equation is iterated in between. */
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use analysis::raster::{rasterize, LabelGrid, RasterParams, GRAY_MATTER, OUTSIDE, WHITE_MATTER};
+use graph::types::ThickSurface;
+
+#[derive(Clone, Debug)]
+pub struct HeatParams {
+    pub raster: RasterParams,
+    pub alpha: f64,
+    pub dt: f64,
+    pub iterations: usize,
+    pub high_temperature: f64,
+    pub low_temperature: f64,
+    pub initial_temperature: f64,
+    pub slice_every: usize,
+    pub steady_state_tolerance: f64,
+}
+
+impl Default for HeatParams {
+    // Same values as helmholtz_sars.py. dx is always 1 grid cell, so dt = dx^2 / (4 * alpha)
+    fn default() -> HeatParams {
+        HeatParams {
+            raster: RasterParams::default(),
+            alpha: 2.0,
+            dt: 1.0 / (4.0 * 2.0),
+            iterations: 128,
+            high_temperature: 100.0,
+            low_temperature: 0.0,
+            initial_temperature: 10.0,
+            slice_every: 16,
+            steady_state_tolerance: 1e-3,
+        }
+    }
+}
+
+impl HeatParams {
+    fn gamma(&self) -> f64 {
+        self.alpha * self.dt
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HeatSolution {
+    pub grid: LabelGrid,
+    // (iteration, field) pairs, every slice_every iterations plus the last one
+    pub slices: Vec<(usize, Vec<f64>)>,
+    pub dt: f64,
+    pub iterations_run: usize,
+    // First iteration after which no gray matter cell changed by more than steady_state_tolerance
+    pub steady_state_iteration: Option<usize>,
+    pub final_max_change: f64,
+    pub final_mean_gray_temperature: f64,
+}
+
+impl HeatSolution {
+    pub fn time_to_steady_state(&self) -> Option<f64> {
+        self.steady_state_iteration.map(|k| k as f64 * self.dt)
+    }
+}
+
+pub fn solve(ts: &ThickSurface, p: &HeatParams) -> Result<HeatSolution, String> {
+    if p.gamma() > 0.25 {
+        return Err(format!("alpha * dt = {} > 0.25, the explicit scheme would be unstable", p.gamma()));
+    }
+    let grid = rasterize(ts, &p.raster);
+    let (width, height) = (grid.width, grid.height);
+    let labels = &grid.labels;
+    let mut u: Vec<f64> = labels
+        .iter()
+        .map(|l| match *l {
+            WHITE_MATTER => p.high_temperature,
+            OUTSIDE => p.low_temperature,
+            _ => p.initial_temperature,
+        })
+        .collect();
+    let mut next = u.clone();
+    let mut slices = vec![(0, u.clone())];
+    let mut steady_state_iteration = None;
+    let mut max_change = 0.0;
+
+    for k in 1..p.iterations + 1 {
+        max_change = 0.0;
+        for row in 1..height - 1 {
+            for col in 1..width - 1 {
+                let i = row * width + col;
+                if labels[i] == GRAY_MATTER {
+                    let laplacian = u[i - width] + u[i + width] + u[i - 1] + u[i + 1] - 4.0 * u[i];
+                    next[i] = u[i] + p.gamma() * laplacian;
+                    max_change = f64::max(max_change, (next[i] - u[i]).abs());
+                }
+            }
+        }
+        std::mem::swap(&mut u, &mut next);
+
+        if steady_state_iteration.is_none() && max_change < p.steady_state_tolerance {
+            steady_state_iteration = Some(k);
+        }
+        if k % p.slice_every.max(1) == 0 || k == p.iterations {
+            slices.push((k, u.clone()));
+        }
+    }
+
+    let gray_cells = grid.count(GRAY_MATTER);
+    let gray_sum: f64 = u.iter().zip(labels.iter()).filter(|(_, l)| **l == GRAY_MATTER).map(|(t, _)| *t).sum();
+    let final_mean_gray_temperature = if gray_cells == 0 { 0.0 } else { gray_sum / gray_cells as f64 };
+
+    Ok(HeatSolution {
+        grid,
+        slices,
+        dt: p.dt,
+        iterations_run: p.iterations,
+        steady_state_iteration,
+        final_max_change: max_change,
+        final_mean_gray_temperature,
+    })
+}
+
+// One CSV per time slice, heat_<iteration>.csv, each line a row of the grid. Returns the files written
+pub fn write_slices(solution: &HeatSolution, output_dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for (k, field) in &solution.slices {
+        let path = format!("{}/heat_{:06}.csv", output_dir, k);
+        let mut file = File::create(&path)?;
+        files.push(path);
+        for row in field.chunks(solution.grid.width) {
+            let line: Vec<String> = row.iter().map(|t| t.to_string()).collect();
+            writeln!(file, "{}", line.join(","))?;
+        }
+    }
+    Ok(files)
+}
+
+pub fn write_summary(solution: &HeatSolution, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(file_path)?;
+    writeln!(file, "iterations,dt,steady_state_iteration,time_to_steady_state,final_max_change,final_mean_gray_temperature")?;
+    writeln!(
+        file,
+        "{},{},{},{},{},{}",
+        solution.iterations_run,
+        solution.dt,
+        solution.steady_state_iteration.map(|k| k.to_string()).unwrap_or(String::from("NA")),
+        solution.time_to_steady_state().map(|t| t.to_string()).unwrap_or(String::from("NA")),
+        solution.final_max_change,
+        solution.final_mean_gray_temperature
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+
+    #[test]
+    fn annulus_heats_up_and_settles() {
+        let ts = circular_thick_surface(1.0, 0.3, 200);
+        let p = HeatParams {
+            raster: RasterParams {
+                width: 64,
+                height: 64,
+                ..RasterParams::default()
+            },
+            iterations: 2000,
+            ..HeatParams::default()
+        };
+        let solution = solve(&ts, &p).unwrap();
+        assert!(solution.grid.count(GRAY_MATTER) > 0);
+        assert!(solution.grid.count(WHITE_MATTER) > 0);
+        assert!(solution.steady_state_iteration.is_some());
+        assert!(solution.final_mean_gray_temperature > p.low_temperature && solution.final_mean_gray_temperature < p.high_temperature);
+    }
+
+    #[test]
+    fn unstable_time_step_is_rejected() {
+        let ts = circular_thick_surface(1.0, 0.3, 50);
+        let p = HeatParams { dt: 1.0, ..HeatParams::default() };
+        assert!(solve(&ts, &p).is_err());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod box_counting;
+pub mod helmholtz;
+pub mod raster;
+pub mod thickness;
@@ -0,0 +1,124 @@
+/* Local cortical thickness, one value per correspondence between the outer and the inner layer.
+   The T recorder only gives a global log10(gray area / outer perimeter); this shows where the gray matter got
+   stretched (thin) or compressed (thick) by the folding. */
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use graph::distance_between_nodes;
+use graph::types::{ThickSurface, INNER, OUTER};
+use linalg_helpers::dist_to_segment;
+use stitcher::types::Stitching;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThicknessStats {
+    pub count: usize,
+    pub mean: f64,
+    pub sd: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/* Distance between every pair of stitched nodes. The stitching is only computed once, so pairs where one of the
+   nodes has since been merged away are skipped */
+pub fn from_stitching(ts: &ThickSurface, s: &Stitching) -> Vec<f64> {
+    let (outer, inner) = (&ts.layers[OUTER], &ts.layers[INNER]);
+    s.positions(ts)
+        .iter()
+        .map(|(o, i)| distance_between_nodes(&outer.nodes[*o], &inner.nodes[*i]))
+        .collect()
+}
+
+// For every outer node, the distance to the closest point (not node) of the inner layer
+pub fn from_nearest_points(ts: &ThickSurface) -> Vec<f64> {
+    let inner = &ts.layers[INNER];
+    ts.layers[OUTER]
+        .nodes
+        .iter()
+        .map(|o| {
+            inner
+                .nodes
+                .iter()
+                .map(|i| dist_to_segment(o.x, o.y, i.x, i.y, i.next(inner).x, i.next(inner).y))
+                .fold(f64::INFINITY, f64::min)
+        })
+        .collect()
+}
+
+pub fn stats(thicknesses: &[f64]) -> ThicknessStats {
+    let n = thicknesses.len() as f64;
+    let mean = thicknesses.iter().sum::<f64>() / n;
+    let var = thicknesses.iter().map(|t| (t - mean) * (t - mean)).sum::<f64>() / n;
+    ThicknessStats {
+        count: thicknesses.len(),
+        mean,
+        sd: var.sqrt(),
+        min: thicknesses.iter().cloned().fold(f64::INFINITY, f64::min),
+        max: thicknesses.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+// (bin start, bin end, count) for num_bins equally wide bins between the smallest and the largest thickness. No bins
+// with num_bins = 0
+pub fn histogram(thicknesses: &[f64], num_bins: usize) -> Vec<(f64, f64, usize)> {
+    if num_bins == 0 {
+        return vec![];
+    }
+    let s = stats(thicknesses);
+    let width = if s.max > s.min { (s.max - s.min) / num_bins as f64 } else { 1.0 };
+    let mut counts = vec![0; num_bins];
+    for t in thicknesses {
+        let bin = (((t - s.min) / width) as usize).min(num_bins - 1);
+        counts[bin] += 1;
+    }
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (s.min + i as f64 * width, s.min + (i + 1) as f64 * width, *c))
+        .collect()
+}
+
+pub fn write_histogram_csv(thicknesses: &[f64], num_bins: usize, file_path: &str) -> Result<(), Box<dyn Error>> {
+    if num_bins == 0 {
+        return Err(Box::from("a histogram needs at least one bin"));
+    }
+    let mut file = File::create(file_path)?;
+    writeln!(file, "bin_start,bin_end,count")?;
+    for (start, end, count) in histogram(thicknesses, num_bins) {
+        writeln!(file, "{},{},{}", start, end, count)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use stitcher::stitch_default;
+
+    #[test]
+    fn concentric_circles_have_constant_thickness() {
+        let ts = circular_thick_surface(1.0, 0.25, 60);
+        let nearest = stats(&from_nearest_points(&ts));
+        assert!((nearest.mean - 0.25).abs() < 0.01);
+        assert!(nearest.sd < 0.01);
+
+        let stitched = stats(&from_stitching(&ts, &stitch_default(&ts)));
+        assert!(stitched.count >= 60);
+        assert!(stitched.min >= nearest.min - 1e-9);
+    }
+
+    #[test]
+    fn histogram_keeps_every_value() {
+        let v = vec![0.1, 0.2, 0.2, 0.3, 0.9];
+        let h = histogram(&v, 4);
+        assert_eq!(h.iter().map(|(_, _, c)| c).sum::<usize>(), v.len());
+        assert_eq!(h[3].2, 1);
+
+        assert!(histogram(&v, 0).is_empty());
+        let path = std::env::temp_dir().join("sann_no_bins_hist.csv");
+        let _ = std::fs::remove_file(&path);
+        assert!(write_histogram_csv(&v, 0, path.to_str().unwrap()).is_err());
+        assert!(!path.exists());
+    }
+}
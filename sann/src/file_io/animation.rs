@@ -0,0 +1,352 @@
+/* Movies of a run without a window: every few steps the surface goes through analysis::raster (no OpenGL involved)
+   and the frame is added to a GIF or an animated PNG, or written as the next PNG of a numbered sequence. The framing is
+   fixed on the first frame, so the surface doesn't jump around as it folds; padding leaves room for it to grow. */
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use analysis::raster::{rasterize, rasterize_with, LabelGrid, RasterParams, GRAY_MATTER, OUTSIDE, WHITE_MATTER};
+use graph::types::ThickSurface;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationFormat {
+    // animation.gif
+    Gif,
+    // frame_<number>.png
+    PngSequence,
+    // animation.png, which viewers that don't know APNG show as its first frame
+    Apng,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationParams {
+    pub every: u64,
+    pub raster: RasterParams,
+    pub format: AnimationFormat,
+    // How long each frame of the GIF or APNG is shown
+    pub frame_delay_ms: u16,
+}
+
+impl Default for AnimationParams {
+    fn default() -> AnimationParams {
+        AnimationParams {
+            every: 100,
+            raster: RasterParams {
+                width: 256,
+                height: 256,
+                padding: 0.15,
+            },
+            format: AnimationFormat::Gif,
+            frame_delay_ms: 100,
+        }
+    }
+}
+
+// The labels are the GIF's palette indices, so frames look like LabelGrid::write_png's
+fn palette() -> Vec<u8> {
+    let mut ret = vec![0; 9];
+    for (label, level) in [(OUTSIDE, 0), (GRAY_MATTER, 128), (WHITE_MATTER, 255)] {
+        for c in 0..3 {
+            ret[label as usize * 3 + c] = level;
+        }
+    }
+    ret
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&crc.finalize().to_be_bytes())?;
+    Ok(())
+}
+
+// The data of every IDAT chunk of a PNG, in order
+fn idat_chunks(png: &[u8]) -> Result<Vec<&[u8]>, Box<dyn Error>> {
+    let mut ret = Vec::new();
+    let mut at = PNG_SIGNATURE.len();
+    while at + 8 <= png.len() {
+        let length = u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]) as usize;
+        let (kind, data) = (&png[at + 4..at + 8], at + 8);
+        if data + length + 4 > png.len() {
+            return Err(From::from("truncated PNG chunk"));
+        }
+        if kind == b"IDAT" {
+            ret.push(&png[data..data + length]);
+        }
+        at = data + length + 4;
+    }
+    Ok(ret)
+}
+
+/* png 0.16 only writes still images, so animated PNGs are put together here: every frame is encoded as a still PNG
+   in memory, and its IDAT chunks are copied over (as fdAT chunks after the first frame) behind an fcTL with the delay.
+   How many frames there are is only known at the end, so finish goes back and fills in acTL */
+struct ApngWriter {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    delay_ms: u16,
+    frames: u32,
+    // Every fcTL and fdAT chunk takes the next one
+    sequence: u32,
+}
+
+impl ApngWriter {
+    // Where acTL starts: after the signature and IHDR's 13 bytes of data plus 12 of length, type and CRC
+    const ACTL_AT: u64 = 8 + 25;
+
+    fn create(file_path: &str, width: u32, height: u32, delay_ms: u16) -> Result<ApngWriter, Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(file_path)?);
+        file.write_all(&PNG_SIGNATURE)?;
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        // 8 bit grayscale, like LabelGrid::write_png
+        ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+        write_chunk(&mut file, b"IHDR", &ihdr)?;
+        let mut w = ApngWriter {
+            file,
+            width,
+            height,
+            delay_ms,
+            frames: 0,
+            sequence: 0,
+        };
+        w.write_actl()?;
+        Ok(w)
+    }
+
+    // Number of frames, then 0 for looping forever
+    fn write_actl(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut actl = Vec::new();
+        actl.extend_from_slice(&self.frames.to_be_bytes());
+        actl.extend_from_slice(&0u32.to_be_bytes());
+        write_chunk(&mut self.file, b"acTL", &actl)
+    }
+
+    fn add_frame(&mut self, grid: &LabelGrid) -> Result<(), Box<dyn Error>> {
+        let mut png = Vec::new();
+        grid.encode_png(&mut png)?;
+        let mut fctl = Vec::new();
+        fctl.extend_from_slice(&self.sequence.to_be_bytes());
+        fctl.extend_from_slice(&self.width.to_be_bytes());
+        fctl.extend_from_slice(&self.height.to_be_bytes());
+        // No offset, delay_ms / 1000 seconds, and each frame replaces the last one whole
+        fctl.extend_from_slice(&[0; 8]);
+        fctl.extend_from_slice(&self.delay_ms.to_be_bytes());
+        fctl.extend_from_slice(&1000u16.to_be_bytes());
+        fctl.extend_from_slice(&[0, 0]);
+        write_chunk(&mut self.file, b"fcTL", &fctl)?;
+        self.sequence += 1;
+        for data in idat_chunks(&png)? {
+            if self.frames == 0 {
+                write_chunk(&mut self.file, b"IDAT", data)?;
+            } else {
+                let mut fdat = self.sequence.to_be_bytes().to_vec();
+                fdat.extend_from_slice(data);
+                write_chunk(&mut self.file, b"fdAT", &fdat)?;
+                self.sequence += 1;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        write_chunk(&mut self.file, b"IEND", &[])?;
+        self.file.seek(SeekFrom::Start(ApngWriter::ACTL_AT))?;
+        self.write_actl()?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+pub struct Animator {
+    params: AnimationParams,
+    folder: String,
+    // Scale and centre of the first frame
+    framing: Option<(f64, (f64, f64))>,
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+    apng: Option<ApngWriter>,
+    frames_written: usize,
+}
+
+impl Animator {
+    pub fn create(folder: &str, params: &AnimationParams) -> Result<Animator, Box<dyn Error>> {
+        let gif = match params.format {
+            AnimationFormat::Gif => {
+                let file = BufWriter::new(File::create(format!("{}/animation.gif", folder))?);
+                let (w, h) = (params.raster.width as u16, params.raster.height as u16);
+                let mut encoder = gif::Encoder::new(file, w, h, &palette())?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                Some(encoder)
+            }
+            _ => None,
+        };
+        let apng = match params.format {
+            AnimationFormat::Apng => Some(ApngWriter::create(
+                &format!("{}/animation.png", folder),
+                params.raster.width as u32,
+                params.raster.height as u32,
+                params.frame_delay_ms,
+            )?),
+            _ => None,
+        };
+        Ok(Animator {
+            params: params.clone(),
+            folder: String::from(folder),
+            framing: None,
+            gif,
+            apng,
+            frames_written: 0,
+        })
+    }
+
+    pub fn wants(&self, timestep: u64) -> bool {
+        timestep.is_multiple_of(self.params.every)
+    }
+
+    fn frame_of(&mut self, ts: &ThickSurface) -> LabelGrid {
+        let p = &self.params.raster;
+        match self.framing {
+            Some((scale, center)) => rasterize_with(ts, p.width, p.height, scale, center),
+            None => {
+                let grid = rasterize(ts, p);
+                self.framing = Some((grid.scale, grid.center));
+                grid
+            }
+        }
+    }
+
+    pub fn add_frame(&mut self, ts: &ThickSurface) -> Result<(), Box<dyn Error>> {
+        let grid = self.frame_of(ts);
+        match &mut self.gif {
+            Some(encoder) => {
+                let frame = gif::Frame {
+                    width: grid.width as u16,
+                    height: grid.height as u16,
+                    // GIF delays are in hundredths of a second
+                    delay: self.params.frame_delay_ms / 10,
+                    buffer: Cow::Borrowed(&grid.labels),
+                    ..gif::Frame::default()
+                };
+                encoder.write_frame(&frame)?;
+            }
+            None => match &mut self.apng {
+                Some(apng) => apng.add_frame(&grid)?,
+                None => grid.write_png(&format!("{}/frame_{:06}.png", self.folder, self.frames_written))?,
+            },
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    // animation.gif, animation.png or the frame_*.png written so far
+    pub fn files(&self) -> Vec<String> {
+        match self.params.format {
+            AnimationFormat::Gif => vec![format!("{}/animation.gif", self.folder)],
+            AnimationFormat::Apng => vec![format!("{}/animation.png", self.folder)],
+            AnimationFormat::PngSequence => (0..self.frames_written).map(|i| format!("{}/frame_{:06}.png", self.folder, i)).collect(),
+        }
+    }
+
+    // Once the run is over. GIFs finish themselves when dropped, an APNG needs its frame count filled in
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        match &mut self.apng {
+            Some(apng) => apng.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn frames_keep_the_first_framing() {
+        let folder = temp_dir().join("sann_animation_test");
+        fs::create_dir_all(&folder).unwrap();
+        let folder = folder.to_str().unwrap();
+        let p = AnimationParams {
+            format: AnimationFormat::PngSequence,
+            raster: RasterParams {
+                width: 40,
+                height: 40,
+                padding: 0.1,
+            },
+            ..AnimationParams::default()
+        };
+        let mut a = Animator::create(folder, &p).unwrap();
+        assert!(a.wants(300) && !a.wants(301));
+
+        a.add_frame(&circular_thick_surface(1.0, 0.3, 40)).unwrap();
+        // Twice as big: the first frame's scale cuts it off instead of shrinking it to fit
+        let bigger = circular_thick_surface(2.0, 0.6, 40);
+        assert_eq!(a.frame_of(&bigger).count(OUTSIDE), 0);
+        a.add_frame(&bigger).unwrap();
+        assert_eq!(a.frames_written, 2);
+        assert_eq!(a.files(), vec![format!("{}/frame_000000.png", folder), format!("{}/frame_000001.png", folder)]);
+        assert!(fs::metadata(format!("{}/frame_000001.png", folder)).is_ok());
+
+        let mut g = Animator::create(folder, &AnimationParams { raster: p.raster.clone(), ..AnimationParams::default() }).unwrap();
+        g.add_frame(&bigger).unwrap();
+        drop(g);
+        assert!(fs::read(format!("{}/animation.gif", folder)).unwrap().starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn apng_frames_are_counted_and_sequenced() {
+        let folder = temp_dir().join("sann_apng_test");
+        fs::create_dir_all(&folder).unwrap();
+        let folder = folder.to_str().unwrap();
+        let p = AnimationParams {
+            format: AnimationFormat::Apng,
+            raster: RasterParams {
+                width: 30,
+                height: 20,
+                padding: 0.1,
+            },
+            ..AnimationParams::default()
+        };
+        let mut a = Animator::create(folder, &p).unwrap();
+        for r in [1.0, 1.2, 1.4] {
+            a.add_frame(&circular_thick_surface(r, 0.3, 40)).unwrap();
+        }
+        a.finish().unwrap();
+        drop(a);
+
+        let png = fs::read(format!("{}/animation.png", folder)).unwrap();
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let length = u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]) as usize;
+            chunks.push((String::from_utf8(png[at + 4..at + 8].to_vec()).unwrap(), png[at + 8..at + 8 + length].to_vec()));
+            at += length + 12;
+        }
+        let kinds: Vec<&str> = chunks.iter().map(|(k, _)| k.as_str()).filter(|k| *k != "IDAT" && *k != "fdAT").collect();
+        assert_eq!(kinds, vec!["IHDR", "acTL", "fcTL", "fcTL", "fcTL", "IEND"]);
+        assert_eq!(chunks[1].1, vec![0, 0, 0, 3, 0, 0, 0, 0]);
+        // fcTL and fdAT share one sequence, with no gaps
+        let sequence: Vec<u32> = chunks
+            .iter()
+            .filter(|(k, _)| k == "fcTL" || k == "fdAT")
+            .map(|(_, d)| u32::from_be_bytes([d[0], d[1], d[2], d[3]]))
+            .collect();
+        assert_eq!(sequence, (0..sequence.len() as u32).collect::<Vec<u32>>());
+        // Still a PNG for anything that only reads the first frame
+        let decoder = png::Decoder::new(File::open(format!("{}/animation.png", folder)).unwrap());
+        let (info, _) = decoder.read_info().unwrap();
+        assert_eq!((info.width, info.height), (30, 20));
+    }
+}
@@ -0,0 +1,93 @@
+/* WKT and GeoJSON for surfaces, as the polygon of graph::polygon: the outer layer is the exterior ring and the inner
+   layer the hole. Readers refuse anything that isn't exactly one such polygon, or that isn't a proper surface. */
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use geo::{Geometry, Polygon};
+use geojson::{Feature, GeoJson};
+use wkt::{ToWkt, TryFromWkt};
+
+use graph::polygon::{polygon_to_thick_surface, thick_surface_to_polygon};
+use graph::types::ThickSurface;
+
+pub fn to_wkt(ts: &ThickSurface) -> String {
+    thick_surface_to_polygon(ts).wkt_string()
+}
+
+pub fn from_wkt(s: &str) -> Result<ThickSurface, Box<dyn Error>> {
+    let p: Polygon<f64> = Polygon::try_from_wkt_str(s.trim()).map_err(|e| format!("not a WKT polygon: {}", e))?;
+    Ok(polygon_to_thick_surface(&p)?)
+}
+
+// A single Feature, so properties can be added by whatever reads it
+pub fn to_geojson(ts: &ThickSurface) -> String {
+    GeoJson::from(Feature {
+        bbox: None,
+        geometry: Some(geojson::Geometry::new(geojson::Value::from(&thick_surface_to_polygon(ts)))),
+        id: None,
+        properties: None,
+        foreign_members: None,
+    })
+    .to_string()
+}
+
+// A Polygon geometry, or a Feature or FeatureCollection holding exactly one
+pub fn from_geojson(s: &str) -> Result<ThickSurface, Box<dyn Error>> {
+    let gj: GeoJson = s.parse()?;
+    let p = match Geometry::<f64>::try_from(gj)? {
+        Geometry::Polygon(p) => p,
+        Geometry::GeometryCollection(c) if c.0.len() == 1 => match c.0.into_iter().next() {
+            Some(Geometry::Polygon(p)) => p,
+            _ => return Err(From::from("the only geometry isn't a polygon")),
+        },
+        _ => return Err(From::from("expected exactly one polygon")),
+    };
+    Ok(polygon_to_thick_surface(&p)?)
+}
+
+pub fn write_wkt(ts: &ThickSurface, file_path: &str) -> Result<(), Box<dyn Error>> {
+    writeln!(File::create(file_path)?, "{}", to_wkt(ts))?;
+    Ok(())
+}
+
+pub fn read_wkt(file_path: &str) -> Result<ThickSurface, Box<dyn Error>> {
+    from_wkt(&std::fs::read_to_string(file_path)?).map_err(|e| From::from(format!("{}: {}", file_path, e)))
+}
+
+pub fn write_geojson(ts: &ThickSurface, file_path: &str) -> Result<(), Box<dyn Error>> {
+    writeln!(File::create(file_path)?, "{}", to_geojson(ts))?;
+    Ok(())
+}
+
+pub fn read_geojson(file_path: &str) -> Result<ThickSurface, Box<dyn Error>> {
+    from_geojson(&std::fs::read_to_string(file_path)?).map_err(|e| From::from(format!("{}: {}", file_path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+
+    #[test]
+    fn wkt_and_geojson_read_back_what_was_written() {
+        let ts = circular_thick_surface(1.0, 0.3, 30);
+        let wkt = to_wkt(&ts);
+        assert!(wkt.starts_with("POLYGON(("));
+        let geojson = to_geojson(&ts);
+        // The WKT parser can be off by the last bit
+        for back in [from_wkt(&wkt).unwrap(), from_geojson(&geojson).unwrap()] {
+            for (g, h) in ts.layers.iter().zip(back.layers.iter()) {
+                for ((x1, y1), (x2, y2)) in g.to_vec_of_points().iter().zip(h.to_vec_of_points().iter()) {
+                    assert!((x1 - x2).abs() < 1e-12 && (y1 - y2).abs() < 1e-12);
+                }
+                assert_eq!(g.nodes.len(), h.nodes.len());
+            }
+        }
+
+        assert!(from_wkt("POLYGON((0 0,1 0,1 1,0 0))").is_err());
+        assert!(from_wkt("LINESTRING(0 0,1 1)").is_err());
+        assert!(from_geojson("{\"type\":\"Point\",\"coordinates\":[0.0,0.0]}").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+/* Provenance of a run, written next to its outputs: the effective parameters, what build and stepper ran them with
+   which seed, when, for how long and why it stopped, the last recorder values and every file the run produced.
+   It's written once when the run starts (so a run that dies still says what it was) and again when it ends. */
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use file_io::params::{params_to_toml, seed_to_toml};
+use types::Params;
+
+pub const STILL_RUNNING: &str = "still running";
+
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    pub params: Params,
+    // The function that steps the simulation
+    pub stepper: String,
+    pub seed: u64,
+    pub started: SystemTime,
+    pub finished: Option<SystemTime>,
+    pub total_steps: u64,
+    pub stopping_reason: String,
+    // Recorder name and value, in the order of params.recorders
+    pub final_recorders: Vec<(String, f64)>,
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    pub fn start(params: &Params, stepper: &str, seed: u64) -> Manifest {
+        Manifest {
+            params: params.clone(),
+            stepper: String::from(stepper),
+            seed,
+            started: SystemTime::now(),
+            finished: None,
+            total_steps: 0,
+            stopping_reason: String::from(STILL_RUNNING),
+            final_recorders: vec![],
+            files: vec![],
+        }
+    }
+
+    pub fn finish(&mut self, total_steps: u64, stopping_reason: &str, final_recorders: Vec<(String, f64)>, files: Vec<String>) {
+        self.finished = Some(SystemTime::now());
+        self.total_steps = total_steps;
+        self.stopping_reason = String::from(stopping_reason);
+        self.final_recorders = final_recorders;
+        self.files = files;
+    }
+
+    pub fn to_toml(&self) -> toml::Value {
+        let mut m = toml::value::Table::new();
+        let mut put = |k: &str, v: toml::Value| {
+            m.insert(String::from(k), v);
+        };
+        put("crate", toml::Value::String(String::from(env!("CARGO_PKG_NAME"))));
+        put("version", toml::Value::String(String::from(env!("CARGO_PKG_VERSION"))));
+        put("stepper", toml::Value::String(self.stepper.clone()));
+        put("seed", seed_to_toml(self.seed));
+        put("started", datetime(self.started));
+        if let Some(f) = self.finished {
+            put("finished", datetime(f));
+            let seconds = f.duration_since(self.started).unwrap_or(Duration::from_secs(0)).as_secs_f64();
+            put("duration_seconds", toml::Value::Float(seconds));
+        }
+        put("total_steps", toml::Value::Integer(self.total_steps as i64));
+        put("stopping_reason", toml::Value::String(self.stopping_reason.clone()));
+        put("files", toml::Value::Array(self.files.iter().map(|f| toml::Value::String(f.clone())).collect()));
+        let mut recorders = toml::value::Table::new();
+        for (name, value) in &self.final_recorders {
+            recorders.insert(name.clone(), toml::Value::Float(*value));
+        }
+        put("final_recorders", toml::Value::Table(recorders));
+        put("params", params_to_toml(&self.params));
+        toml::Value::Table(m)
+    }
+
+    pub fn write(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        write!(File::create(file_path)?, "{}", toml::to_string(&self.to_toml())?)?;
+        Ok(())
+    }
+}
+
+// output.csv -> output.manifest.toml, for runs that have no output folder of their own
+pub fn manifest_path(output_file_path: &str) -> String {
+    Path::new(output_file_path).with_extension("manifest.toml").to_string_lossy().into_owned()
+}
+
+// UTC, to the second, like 2021-03-04T05:06:07Z
+pub fn utc_timestamp(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let (days, rest) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // Days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+fn datetime(t: SystemTime) -> toml::Value {
+    match utc_timestamp(t).parse::<toml::value::Datetime>() {
+        Ok(d) => toml::Value::Datetime(d),
+        Err(_) => toml::Value::String(utc_timestamp(t)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_io::params::params_from_toml;
+
+    #[test]
+    fn manifest_holds_what_the_run_was() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723)), "2000-02-29T01:02:03Z");
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+
+        let params = Params {
+            recorders: vec![String::from("energy"), String::from("outer perimeter")],
+            seed: Some(7),
+            ..Params::default()
+        };
+        let mut m = Manifest::start(&params, "step", 7);
+        let running = m.to_toml();
+        assert_eq!(running["stopping_reason"].as_str(), Some(STILL_RUNNING));
+        assert!(running.get("finished").is_none());
+
+        m.finish(100, "reached 100 steps", vec![(String::from("energy"), 1.5), (String::from("outer perimeter"), 6.25)], vec![String::from("out/snapshots.csv")]);
+        let t = m.to_toml();
+        assert_eq!(t["version"].as_str(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(t["seed"].as_integer(), Some(7));
+        assert_eq!(t["total_steps"].as_integer(), Some(100));
+        assert!(t["finished"].is_datetime() && t["duration_seconds"].as_float().unwrap() >= 0.0);
+        assert_eq!(t["final_recorders"]["outer perimeter"].as_float(), Some(6.25));
+        assert_eq!(t["files"].as_array().unwrap().len(), 1);
+        // The params table is a parameters file of its own
+        let back = params_from_toml(&t["params"]).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", params));
+        assert!(toml::to_string(&t).unwrap().parse::<toml::Value>().is_ok());
+
+        // Past i64::MAX the seed goes in as a string
+        let t = Manifest::start(&params, "step", u64::MAX).to_toml();
+        assert_eq!(t["seed"].as_str(), Some("18446744073709551615"));
+    }
+}
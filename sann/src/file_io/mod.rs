@@ -1,42 +1,70 @@
+pub mod animation;
+pub mod gis;
+pub mod manifest;
+pub mod params;
+pub mod points;
 pub mod recorders;
+pub mod replay;
+pub mod snapshots;
+pub mod svg;
+pub mod trajectory;
 
-use std::f64::consts::PI;
+use graph::types::ThickSurface;
+use std::error::Error;
+use std::path::Path;
 use types;
 
+/* The surface a command works on: a finished run's folder (its dados_out.csv and dados_in.csv), or a .wkt or .geojson
+   file. Whichever it is has to be a proper surface. Comes with the folder the command should write to */
+pub fn read_surface(path: &str) -> Result<(ThickSurface, String), Box<dyn Error>> {
+    let p = Path::new(path);
+    if p.is_dir() {
+        let ts = points::read_thick_surface(&format!("{}/dados_out.csv", path), &format!("{}/dados_in.csv", path))?;
+        return Ok((ts, String::from(path)));
+    }
+    let ts = match p.extension().and_then(|e| e.to_str()) {
+        Some("wkt") => gis::read_wkt(path)?,
+        Some("geojson") | Some("json") => gis::read_geojson(path)?,
+        _ => return Err(From::from(format!("{}: expected a run's folder, a .wkt or a .geojson file", path))),
+    };
+    let folder = match p.parent().map(|f| f.to_string_lossy().into_owned()) {
+        Some(ref f) if !f.is_empty() => f.clone(),
+        _ => String::from("."),
+    };
+    Ok((ts, folder))
+}
+
+// Unknown keys only get a warning, a bad value stops everything before the run starts
 pub fn toml_table_to_params(table: toml::Value) -> types::Params {
-    match table {
-        toml::Value::Table(m) => {
-            let initial_radius = m.get("initial_radius").unwrap().as_float().unwrap();
-            let initial_thickness = m.get("initial_thickness").unwrap().as_float().unwrap();
-            let initial_area = PI * (initial_radius.powf(2.0) - (initial_radius - initial_thickness).powf(2.0));
-            types::Params {
-                initial_thickness: initial_thickness,
-                initial_radius: initial_radius,
-                initial_gray_matter_area: initial_area,
-                initial_num_points: m.get("initial_num_points").unwrap().as_integer().unwrap() as usize,
-                initial_temperature: m.get("initial_temperature").unwrap().as_float().unwrap(),
-                compression_factor: m.get("compression_factor").unwrap().as_float().unwrap(),
-                softness_factor: m.get("softness_factor").unwrap().as_float().unwrap(),
-                how_smooth: m.get("how_smooth").unwrap().as_integer().unwrap() as usize,
-                max_merge_steps_away: m.get("max_merge_steps_away").unwrap().as_integer().unwrap() as usize,
-                node_addition_threshold: m.get("node_addition_threshold").unwrap().as_float().unwrap(),
-                node_deletion_threshold: m.get("node_deletion_threshold").unwrap().as_float().unwrap(),
-                low_high: (
-                    m.get("low_high").unwrap().as_array().unwrap()[0].as_float().unwrap(),
-                    m.get("low_high").unwrap().as_array().unwrap()[1].as_float().unwrap(),
-                ),
-                recorders: m
-                    .get("recorders")
-                    .unwrap()
-                    .as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|x| String::from(x.as_str().unwrap()))
-                    .collect(),
-                temperature_param: m.get("temperature_param").unwrap().as_float().unwrap(),
-                output_file_path: String::from(m.get("output_file_path").unwrap().as_str().unwrap()),
-            }
+    for k in params::unknown_keys(&table) {
+        eprintln!("Warning: unknown parameter \"{}\" ignored", k);
+    }
+    match params::params_from_toml(&table) {
+        Ok(p) => p,
+        Err(e) => panic!("Invalid parameters: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::types::OUTER;
+    use std::env::temp_dir;
+
+    #[test]
+    fn surfaces_come_from_folders_and_gis_files() {
+        let folder = temp_dir().join("sann_read_surface_test");
+        std::fs::create_dir_all(&folder).unwrap();
+        let folder = folder.to_str().unwrap();
+        let ts = circular_thick_surface(1.0, 0.3, 30);
+        snapshots::write_latest(&ts, folder).unwrap();
+        gis::write_geojson(&ts, &format!("{}/surface.geojson", folder)).unwrap();
+        for path in [String::from(folder), format!("{}/surface.geojson", folder)] {
+            let (back, written_to) = read_surface(&path).unwrap();
+            assert_eq!(back.layers[OUTER].nodes.len(), 30);
+            assert_eq!(written_to, folder);
         }
-        _ => panic!("No key-value table found in parameters.toml"),
+        assert!(read_surface(&format!("{}/dados_out.csv", folder)).is_err());
     }
 }
@@ -0,0 +1,1082 @@
+/* Reading, checking and writing back parameters.toml.
+   Every key is optional (missing ones take Params::default()'s value), but a key that is there has to have the right
+   type and a sensible value, and the error says which key it was. */
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use analysis::helmholtz::HeatParams;
+use analysis::raster::RasterParams;
+use file_io::animation::{AnimationFormat, AnimationParams};
+use file_io::recorders::{recorder_info, Cadence, RecordEvent, EVENT_NAMES};
+use file_io::snapshots::SnapshotLayout;
+use file_io::svg::{LayerStyle, SvgParams, FIELD_NAMES, STITCHING_COLOUR};
+use file_io::trajectory::TrajectoryParams;
+use graph::pins::{PinRange, PinnedRegion};
+use graph::refine::AdaptiveRefinement;
+use graph::separation::Separation;
+use graph::types::{INNER, OUTER};
+use types::{annulus_area, Params};
+
+const KNOWN_KEYS: [&str; 21] = [
+    "initial_thickness",
+    "initial_radius",
+    "initial_num_points",
+    "initial_temperature",
+    "compression_factor",
+    "softness_factor",
+    "how_smooth",
+    "max_merge_steps_away",
+    "node_addition_threshold",
+    "node_deletion_threshold",
+    "low_high",
+    "recorders",
+    "temperature_param",
+    "output_file_path",
+    "remesh_every",
+    "remesh_spacing",
+    "export_num_points",
+    "snapshot_every",
+    "snapshot_layout",
+    "debug_checks",
+    "seed",
+];
+
+const KNOWN_TABLES: [&str; 7] = [
+    "adaptive_refinement",
+    "separation",
+    "pinned",
+    "helmholtz",
+    "svg",
+    "animation",
+    "trajectory",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamsError {
+    pub key: String,
+    pub problem: String,
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" {}", self.key, self.problem)
+    }
+}
+
+impl Error for ParamsError {}
+
+fn as_f64(v: &toml::Value) -> Option<f64> {
+    match v {
+        toml::Value::Float(f) => Some(*f),
+        toml::Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+// 8.0 is accepted as 8, 8.5 isn't
+fn as_usize(v: &toml::Value) -> Option<usize> {
+    match v {
+        toml::Value::Integer(i) if *i >= 0 => Some(*i as usize),
+        toml::Value::Float(f) if *f >= 0.0 && f.fract() == 0.0 => Some(*f as usize),
+        _ => None,
+    }
+}
+
+// TOML integers stop at i64::MAX, so seeds past it are written as strings of digits (see seed_to_toml)
+fn as_seed(v: &toml::Value) -> Option<u64> {
+    match v {
+        toml::Value::String(s) => s.parse().ok(),
+        _ => as_usize(v).map(|n| n as u64),
+    }
+}
+
+pub fn seed_to_toml(seed: u64) -> toml::Value {
+    if seed <= i64::MAX as u64 {
+        toml::Value::Integer(seed as i64)
+    } else {
+        toml::Value::String(seed.to_string())
+    }
+}
+
+fn as_bool(v: &toml::Value) -> Option<bool> {
+    v.as_bool()
+}
+
+fn as_string(v: &toml::Value) -> Option<String> {
+    v.as_str().map(String::from)
+}
+
+fn as_snapshot_layout(v: &toml::Value) -> Option<SnapshotLayout> {
+    match v.as_str()? {
+        "per_step" => Some(SnapshotLayout::PerStep),
+        "appended" => Some(SnapshotLayout::Appended),
+        _ => None,
+    }
+}
+
+fn as_strings(v: &toml::Value) -> Option<Vec<String>> {
+    v.as_array()?.iter().map(as_string).collect()
+}
+
+fn as_pair(v: &toml::Value) -> Option<(f64, f64)> {
+    match v.as_array() {
+        Some(a) if a.len() == 2 => Some((as_f64(&a[0])?, as_f64(&a[1])?)),
+        _ => None,
+    }
+}
+
+fn read<T>(m: &toml::value::Table, key: &str, expected: &str, convert: fn(&toml::Value) -> Option<T>) -> Result<Option<T>, ParamsError> {
+    match m.get(key) {
+        None => Ok(None),
+        Some(v) => match convert(v) {
+            Some(t) => Ok(Some(t)),
+            None => Err(ParamsError {
+                key: String::from(key),
+                problem: format!("should be {}, found {}", expected, v),
+            }),
+        },
+    }
+}
+
+const ADAPTIVE_REFINEMENT_KEYS: [&str; 5] = ["curvature_weight", "proximity_weight", "min_scale", "max_scale", "node_budget"];
+
+const SEPARATION_KEYS: [&str; 2] = ["min_distance", "penalty"];
+
+// Of each [[pinned]] table
+const PINNED_KEYS: [&str; 3] = ["layers", "angles", "indices"];
+
+const HEAT_KEYS: [&str; 10] = [
+    "grid_size",
+    "padding",
+    "alpha",
+    "dt",
+    "iterations",
+    "high_temperature",
+    "low_temperature",
+    "initial_temperature",
+    "slice_every",
+    "steady_state_tolerance",
+];
+
+const SVG_KEYS: [&str; 12] = [
+    "scale",
+    "margin",
+    "gray_matter",
+    "white_matter",
+    "outer_stroke",
+    "inner_stroke",
+    "stroke_width",
+    "hull",
+    "stitching",
+    "stitching_colour",
+    "node_radius",
+    "colour_by",
+];
+
+const ANIMATION_KEYS: [&str; 6] = ["every", "width", "height", "padding", "format", "frame_delay_ms"];
+
+const TRAJECTORY_KEYS: [&str; 2] = ["every", "keyframe_every"];
+
+// The tables whose keys are checked too, unknown ones come out as "table.key" ("pinned[i].key" for [[pinned]])
+const TABLE_KEYS: [(&str, &[&str]); 6] = [
+    ("adaptive_refinement", &ADAPTIVE_REFINEMENT_KEYS),
+    ("separation", &SEPARATION_KEYS),
+    ("helmholtz", &HEAT_KEYS),
+    ("svg", &SVG_KEYS),
+    ("animation", &ANIMATION_KEYS),
+    ("trajectory", &TRAJECTORY_KEYS),
+];
+
+// Keys that nothing reads. They don't stop a run, but they're probably typos
+pub fn unknown_keys(table: &toml::Value) -> Vec<String> {
+    let m = match table {
+        toml::Value::Table(m) => m,
+        _ => return vec![],
+    };
+    let mut ret: Vec<String> = m
+        .keys()
+        .filter(|k| !KNOWN_KEYS.contains(&k.as_str()) && !KNOWN_TABLES.contains(&k.as_str()))
+        .cloned()
+        .collect();
+    for (name, keys) in TABLE_KEYS.iter() {
+        if let Some(toml::Value::Table(t)) = m.get(*name) {
+            ret.extend(t.keys().filter(|k| !keys.contains(&k.as_str())).map(|k| format!("{}.{}", name, k)));
+        }
+    }
+    if let Some(toml::Value::Array(ts)) = m.get("pinned") {
+        for (i, t) in ts.iter().enumerate() {
+            if let toml::Value::Table(t) = t {
+                ret.extend(t.keys().filter(|k| !PINNED_KEYS.contains(&k.as_str())).map(|k| format!("pinned[{}].{}", i, k)));
+            }
+        }
+    }
+    ret
+}
+
+// An optional table, None when it isn't there
+fn sub_table<'a>(table: &'a toml::Value, name: &str) -> Result<Option<&'a toml::value::Table>, ParamsError> {
+    match table.get(name) {
+        None => Ok(None),
+        Some(toml::Value::Table(m)) => Ok(Some(m)),
+        Some(v) => Err(ParamsError {
+            key: String::from(name),
+            problem: format!("should be a table, found {}", v),
+        }),
+    }
+}
+
+fn adaptive_refinement_from_toml(table: &toml::Value) -> Result<Option<AdaptiveRefinement>, ParamsError> {
+    let m = match sub_table(table, "adaptive_refinement")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let d = AdaptiveRefinement::default();
+    // Errors come out of read() with the bare key, this puts the table's name in front
+    let named = |e: ParamsError| ParamsError {
+        key: format!("adaptive_refinement.{}", e.key),
+        ..e
+    };
+    Ok(Some(AdaptiveRefinement {
+        curvature_weight: read(m, "curvature_weight", "a number", as_f64).map_err(named)?.unwrap_or(d.curvature_weight),
+        proximity_weight: read(m, "proximity_weight", "a number", as_f64).map_err(named)?.unwrap_or(d.proximity_weight),
+        min_scale: read(m, "min_scale", "a number", as_f64).map_err(named)?.unwrap_or(d.min_scale),
+        max_scale: read(m, "max_scale", "a number", as_f64).map_err(named)?.unwrap_or(d.max_scale),
+        node_budget: read(m, "node_budget", "a positive integer", as_usize).map_err(named)?.unwrap_or(d.node_budget),
+    }))
+}
+
+fn separation_from_toml(table: &toml::Value) -> Result<Option<Separation>, ParamsError> {
+    let m = match sub_table(table, "separation")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let named = |e: ParamsError| ParamsError {
+        key: format!("separation.{}", e.key),
+        ..e
+    };
+    match read(m, "min_distance", "a number", as_f64).map_err(named)? {
+        Some(min_distance) => Ok(Some(Separation {
+            min_distance,
+            penalty: read(m, "penalty", "a number", as_f64).map_err(named)?,
+        })),
+        None => Err(ParamsError {
+            key: String::from("separation.min_distance"),
+            problem: String::from("is required in a [separation] table"),
+        }),
+    }
+}
+
+// [helmholtz]: the heat analysis of the final surface. Without dt, the largest stable one for alpha
+fn heat_from_toml(table: &toml::Value) -> Result<Option<HeatParams>, ParamsError> {
+    let m = match sub_table(table, "helmholtz")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let d = HeatParams::default();
+    let named = |e: ParamsError| ParamsError {
+        key: format!("helmholtz.{}", e.key),
+        ..e
+    };
+    let (number, whole) = ("a number", "a positive integer");
+    let grid_size = read(m, "grid_size", whole, as_usize).map_err(named)?.unwrap_or(d.raster.width);
+    let alpha = read(m, "alpha", number, as_f64).map_err(named)?.unwrap_or(d.alpha);
+    Ok(Some(HeatParams {
+        raster: RasterParams {
+            width: grid_size,
+            height: grid_size,
+            padding: read(m, "padding", number, as_f64).map_err(named)?.unwrap_or(d.raster.padding),
+        },
+        alpha,
+        dt: read(m, "dt", number, as_f64).map_err(named)?.unwrap_or(1.0 / (4.0 * alpha)),
+        iterations: read(m, "iterations", whole, as_usize).map_err(named)?.unwrap_or(d.iterations),
+        high_temperature: read(m, "high_temperature", number, as_f64).map_err(named)?.unwrap_or(d.high_temperature),
+        low_temperature: read(m, "low_temperature", number, as_f64).map_err(named)?.unwrap_or(d.low_temperature),
+        initial_temperature: read(m, "initial_temperature", number, as_f64).map_err(named)?.unwrap_or(d.initial_temperature),
+        slice_every: read(m, "slice_every", whole, as_usize).map_err(named)?.unwrap_or(d.slice_every),
+        steady_state_tolerance: read(m, "steady_state_tolerance", number, as_f64).map_err(named)?.unwrap_or(d.steady_state_tolerance),
+    }))
+}
+
+fn as_fields(v: &toml::Value) -> Option<Vec<String>> {
+    as_strings(v)?.into_iter().map(|f| if FIELD_NAMES.contains(&f.as_str()) { Some(f) } else { None }).collect()
+}
+
+// [svg]: the figure of the final surface. The stitching is drawn half as wide as the layers
+fn svg_from_toml(table: &toml::Value) -> Result<Option<SvgParams>, ParamsError> {
+    let m = match sub_table(table, "svg")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let d = SvgParams::default();
+    let named = |e: ParamsError| ParamsError {
+        key: format!("svg.{}", e.key),
+        ..e
+    };
+    let (number, colour, flag) = ("a number", "an SVG colour", "true or false");
+    let stroke_width = read(m, "stroke_width", number, as_f64).map_err(named)?.unwrap_or(d.outer.stroke_width);
+    let stitching_colour = read(m, "stitching_colour", colour, as_string).map_err(named)?;
+    let stitching = match read(m, "stitching", flag, as_bool).map_err(named)? {
+        Some(true) => Some(LayerStyle::line(stitching_colour.as_ref().map_or(STITCHING_COLOUR, |c| c.as_str()), stroke_width / 2.0)),
+        _ if stitching_colour.is_some() => {
+            return Err(ParamsError {
+                key: String::from("svg.stitching_colour"),
+                problem: String::from("does nothing without stitching = true"),
+            })
+        }
+        _ => None,
+    };
+    let layer = |stroke_key: &str, fill_key: &str, d: &LayerStyle| -> Result<LayerStyle, ParamsError> {
+        Ok(LayerStyle::filled(
+            &read(m, stroke_key, colour, as_string).map_err(named)?.unwrap_or(d.stroke.clone()),
+            stroke_width,
+            &read(m, fill_key, colour, as_string).map_err(named)?.unwrap_or(d.fill.clone()),
+        ))
+    };
+    Ok(Some(SvgParams {
+        scale: read(m, "scale", number, as_f64).map_err(named)?.unwrap_or(d.scale),
+        margin: read(m, "margin", number, as_f64).map_err(named)?.unwrap_or(d.margin),
+        outer: layer("outer_stroke", "gray_matter", &d.outer)?,
+        inner: layer("inner_stroke", "white_matter", &d.inner)?,
+        hull: if read(m, "hull", flag, as_bool).map_err(named)?.unwrap_or(true) { d.hull } else { None },
+        stitching,
+        node_radius: read(m, "node_radius", number, as_f64).map_err(named)?.unwrap_or(d.node_radius),
+        colour_by: read(m, "colour_by", &format!("an array of {:?}", FIELD_NAMES), as_fields).map_err(named)?.unwrap_or(d.colour_by),
+    }))
+}
+
+const ANIMATION_FORMATS: [(&str, AnimationFormat); 3] = [
+    ("gif", AnimationFormat::Gif),
+    ("png", AnimationFormat::PngSequence),
+    ("apng", AnimationFormat::Apng),
+];
+
+fn as_animation_format(v: &toml::Value) -> Option<AnimationFormat> {
+    ANIMATION_FORMATS.iter().find(|(name, _)| Some(*name) == v.as_str()).map(|(_, f)| *f)
+}
+
+fn as_u16(v: &toml::Value) -> Option<u16> {
+    as_usize(v).filter(|n| *n <= u16::MAX as usize).map(|n| n as u16)
+}
+
+// [animation]: a movie of the run, one frame every `every` steps
+fn animation_from_toml(table: &toml::Value) -> Result<Option<AnimationParams>, ParamsError> {
+    let m = match sub_table(table, "animation")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let d = AnimationParams::default();
+    let named = |e: ParamsError| ParamsError {
+        key: format!("animation.{}", e.key),
+        ..e
+    };
+    let whole = "a positive integer";
+    Ok(Some(AnimationParams {
+        every: read(m, "every", whole, as_usize).map_err(named)?.map_or(d.every, |n| n as u64),
+        raster: RasterParams {
+            width: read(m, "width", whole, as_usize).map_err(named)?.unwrap_or(d.raster.width),
+            height: read(m, "height", whole, as_usize).map_err(named)?.unwrap_or(d.raster.height),
+            padding: read(m, "padding", "a number", as_f64).map_err(named)?.unwrap_or(d.raster.padding),
+        },
+        format: read(m, "format", "\"gif\", \"png\" or \"apng\"", as_animation_format).map_err(named)?.unwrap_or(d.format),
+        frame_delay_ms: read(m, "frame_delay_ms", "a whole number of milliseconds up to 65535", as_u16)
+            .map_err(named)?
+            .unwrap_or(d.frame_delay_ms),
+    }))
+}
+
+// [trajectory]: the whole run in trajectory.bin
+fn trajectory_from_toml(table: &toml::Value) -> Result<Option<TrajectoryParams>, ParamsError> {
+    let m = match sub_table(table, "trajectory")? {
+        None => return Ok(None),
+        Some(m) => m,
+    };
+    let d = TrajectoryParams::default();
+    let named = |e: ParamsError| ParamsError {
+        key: format!("trajectory.{}", e.key),
+        ..e
+    };
+    let whole = "a positive integer";
+    Ok(Some(TrajectoryParams {
+        every: read(m, "every", whole, as_usize).map_err(named)?.map_or(d.every, |n| n as u64),
+        keyframe_every: read(m, "keyframe_every", whole, as_usize).map_err(named)?.map_or(d.keyframe_every, |n| n as u64),
+    }))
+}
+
+fn as_layers(v: &toml::Value) -> Option<Vec<usize>> {
+    as_strings(v)?
+        .iter()
+        .map(|l| match l.as_str() {
+            "outer" => Some(OUTER),
+            "inner" => Some(INNER),
+            _ => None,
+        })
+        .collect()
+}
+
+fn as_index_pair(v: &toml::Value) -> Option<(usize, usize)> {
+    match v.as_array() {
+        Some(a) if a.len() == 2 => Some((as_usize(&a[0])?, as_usize(&a[1])?)),
+        _ => None,
+    }
+}
+
+// [[pinned]] tables, each with layers (default both) and either angles or indices
+fn pinned_from_toml(table: &toml::Value) -> Result<Vec<PinnedRegion>, ParamsError> {
+    let tables = match table.get("pinned") {
+        None => return Ok(vec![]),
+        Some(toml::Value::Array(ts)) => ts,
+        Some(v) => {
+            return Err(ParamsError {
+                key: String::from("pinned"),
+                problem: format!("should be an array of tables ([[pinned]]), found {}", v),
+            })
+        }
+    };
+    let mut ret = Vec::new();
+    for (i, t) in tables.iter().enumerate() {
+        let named = |e: ParamsError| ParamsError {
+            key: format!("pinned[{}].{}", i, e.key),
+            ..e
+        };
+        let m = match t {
+            toml::Value::Table(m) => m,
+            _ => {
+                return Err(ParamsError {
+                    key: format!("pinned[{}]", i),
+                    problem: String::from("should be a table"),
+                })
+            }
+        };
+        let layers = read(m, "layers", "an array of \"outer\" and/or \"inner\"", as_layers).map_err(named)?;
+        let angles = read(m, "angles", "an array of two numbers (degrees)", as_pair).map_err(named)?;
+        let indices = read(m, "indices", "an array of two node ids", as_index_pair).map_err(named)?;
+        let range = match (angles, indices) {
+            (Some((from, to)), None) => PinRange::Angles(from, to),
+            (None, Some((first, last))) => PinRange::Indices(first, last),
+            _ => {
+                return Err(ParamsError {
+                    key: format!("pinned[{}]", i),
+                    problem: String::from("needs either angles or indices, not both"),
+                })
+            }
+        };
+        ret.push(PinnedRegion {
+            layers: layers.unwrap_or(vec![OUTER, INNER]),
+            range,
+        });
+    }
+    Ok(ret)
+}
+
+fn as_events(v: &toml::Value) -> Option<Vec<RecordEvent>> {
+    v.as_array()?
+        .iter()
+        .map(|e| EVENT_NAMES.iter().find(|(name, _)| Some(*name) == e.as_str()).map(|(_, event)| *event))
+        .collect()
+}
+
+fn event_names(events: &[RecordEvent]) -> toml::Value {
+    toml::Value::Array(
+        events
+            .iter()
+            .filter_map(|e| EVENT_NAMES.iter().find(|(_, event)| event == e))
+            .map(|(name, _)| toml::Value::String(String::from(*name)))
+            .collect(),
+    )
+}
+
+const CADENCE_KEYS: [&str; 4] = ["name", "every", "log_spaced", "on"];
+
+// The recorders' names, in order, and the cadences of the ones given as tables
+type Recorders = (Vec<String>, BTreeMap<String, Cadence>);
+
+/* recorders: each one a name, recorded on the running mode's default cadence, or a table with the name and when to
+   record it: { name = "D_box", every = 10000, log_spaced = 5, on = ["new_minimum", "accepted", "end"] }, any of them
+   will do */
+fn recorders_from_toml(m: &toml::value::Table) -> Result<Option<Recorders>, ParamsError> {
+    let entries = match m.get("recorders") {
+        None => return Ok(None),
+        Some(toml::Value::Array(es)) => es,
+        Some(v) => {
+            return Err(ParamsError {
+                key: String::from("recorders"),
+                problem: format!("should be an array of recorder names or tables, found {}", v),
+            })
+        }
+    };
+    let registered = |name: &str, key: String| match recorder_info(name) {
+        Some(_) => Ok(()),
+        None => Err(ParamsError {
+            key,
+            problem: format!("\"{}\" isn't a recorder (`sann list-recorders` lists them)", name),
+        }),
+    };
+    let (mut names, mut cadences) = (Vec::new(), BTreeMap::new());
+    for (i, e) in entries.iter().enumerate() {
+        let t = match e {
+            toml::Value::String(name) => {
+                registered(name, format!("recorders[{}]", i))?;
+                names.push(name.clone());
+                continue;
+            }
+            toml::Value::Table(t) => t,
+            _ => {
+                return Err(ParamsError {
+                    key: format!("recorders[{}]", i),
+                    problem: format!("should be a recorder name or a table, found {}", e),
+                })
+            }
+        };
+        let named = |e: ParamsError| ParamsError {
+            key: format!("recorders[{}].{}", i, e.key),
+            ..e
+        };
+        if let Some(k) = t.keys().find(|k| !CADENCE_KEYS.contains(&k.as_str())) {
+            return Err(ParamsError {
+                key: format!("recorders[{}].{}", i, k),
+                problem: format!("isn't one of {:?}", CADENCE_KEYS),
+            });
+        }
+        let name = match read(t, "name", "a recorder name", as_string).map_err(named)? {
+            Some(n) => n,
+            None => {
+                return Err(ParamsError {
+                    key: format!("recorders[{}].name", i),
+                    problem: String::from("is missing"),
+                })
+            }
+        };
+        registered(&name, format!("recorders[{}].name", i))?;
+        let cadence = Cadence {
+            every: read(t, "every", "a positive integer", as_usize).map_err(named)?.map(|n| n as u64),
+            log_spaced: read(t, "log_spaced", "a positive integer (rows per decade)", as_usize).map_err(named)?.map(|n| n as u64),
+            on: read(t, "on", "an array of \"new_minimum\", \"accepted\" and/or \"end\"", as_events)
+                .map_err(named)?
+                .unwrap_or(vec![]),
+        };
+        let positive = |key: &str, n: Option<u64>| match n {
+            Some(0) => Err(ParamsError {
+                key: format!("recorders[{}].{}", i, key),
+                problem: String::from("must be positive"),
+            }),
+            _ => Ok(()),
+        };
+        positive("every", cadence.every)?;
+        positive("log_spaced", cadence.log_spaced)?;
+        if cadence.every.is_none() && cadence.log_spaced.is_none() && cadence.on.is_empty() {
+            return Err(ParamsError {
+                key: format!("recorders[{}]", i),
+                problem: String::from("needs every, log_spaced or on, or it would never be recorded"),
+            });
+        }
+        names.push(name.clone());
+        cadences.insert(name, cadence);
+    }
+    Ok(Some((names, cadences)))
+}
+
+pub fn params_from_toml(table: &toml::Value) -> Result<Params, ParamsError> {
+    let m = match table {
+        toml::Value::Table(m) => m,
+        _ => {
+            return Err(ParamsError {
+                key: String::from("parameters.toml"),
+                problem: String::from("has no key-value table"),
+            })
+        }
+    };
+    let d = Params::default();
+    let (number, count, whole) = ("a number", "a non-negative integer", "a positive integer");
+    let initial_radius = read(m, "initial_radius", number, as_f64)?.unwrap_or(d.initial_radius);
+    let initial_thickness = read(m, "initial_thickness", number, as_f64)?.unwrap_or(d.initial_thickness);
+    let (recorders, recorder_cadences) = recorders_from_toml(m)?.unwrap_or((d.recorders, d.recorder_cadences));
+    let p = Params {
+        initial_thickness,
+        initial_radius,
+        initial_gray_matter_area: annulus_area(initial_radius, initial_thickness),
+        initial_num_points: read(m, "initial_num_points", count, as_usize)?.unwrap_or(d.initial_num_points),
+        initial_temperature: read(m, "initial_temperature", number, as_f64)?.unwrap_or(d.initial_temperature),
+        compression_factor: read(m, "compression_factor", number, as_f64)?.unwrap_or(d.compression_factor),
+        softness_factor: read(m, "softness_factor", number, as_f64)?.unwrap_or(d.softness_factor),
+        how_smooth: read(m, "how_smooth", count, as_usize)?.unwrap_or(d.how_smooth),
+        max_merge_steps_away: read(m, "max_merge_steps_away", count, as_usize)?.unwrap_or(d.max_merge_steps_away),
+        node_addition_threshold: read(m, "node_addition_threshold", number, as_f64)?.unwrap_or(d.node_addition_threshold),
+        node_deletion_threshold: read(m, "node_deletion_threshold", number, as_f64)?.unwrap_or(d.node_deletion_threshold),
+        low_high: read(m, "low_high", "an array of two numbers", as_pair)?.unwrap_or(d.low_high),
+        recorders,
+        recorder_cadences,
+        temperature_param: read(m, "temperature_param", number, as_f64)?.unwrap_or(d.temperature_param),
+        output_file_path: read(m, "output_file_path", "a string", as_string)?.unwrap_or(d.output_file_path),
+        remesh_every: read(m, "remesh_every", whole, as_usize)?.map(|n| n as u64),
+        remesh_spacing: read(m, "remesh_spacing", number, as_f64)?,
+        export_num_points: read(m, "export_num_points", whole, as_usize)?,
+        snapshot_every: read(m, "snapshot_every", whole, as_usize)?.map_or(d.snapshot_every, |n| n as u64),
+        snapshot_layout: read(m, "snapshot_layout", "\"per_step\" or \"appended\"", as_snapshot_layout)?.unwrap_or(d.snapshot_layout),
+        debug_checks: read(m, "debug_checks", "true or false", as_bool)?.unwrap_or(d.debug_checks),
+        seed: read(m, "seed", whole, as_seed)?,
+        adaptive_refinement: adaptive_refinement_from_toml(table)?,
+        separation: separation_from_toml(table)?,
+        pinned: pinned_from_toml(table)?,
+        heat: heat_from_toml(table)?,
+        svg: svg_from_toml(table)?,
+        animation: animation_from_toml(table)?,
+        trajectory: trajectory_from_toml(table)?,
+    };
+    check(&p)?;
+    Ok(p)
+}
+
+// Values that would make the simulation meaningless (or loop forever)
+pub fn check(p: &Params) -> Result<(), ParamsError> {
+    let mut checks = vec![
+        ("initial_radius", p.initial_radius > 0.0, String::from("must be positive")),
+        (
+            "initial_thickness",
+            p.initial_thickness > 0.0 && p.initial_thickness < p.initial_radius,
+            format!("must be positive and smaller than initial_radius ({})", p.initial_radius),
+        ),
+        ("initial_num_points", p.initial_num_points >= 3, String::from("must be at least 3")),
+        ("compression_factor", p.compression_factor > 0.0, String::from("must be positive")),
+        ("low_high", p.low_high.0 < p.low_high.1, String::from("must have low < high")),
+        ("node_addition_threshold", p.node_addition_threshold > 0.0, String::from("must be positive")),
+        (
+            "node_deletion_threshold",
+            p.node_deletion_threshold >= 0.0 && p.node_deletion_threshold < p.node_addition_threshold,
+            format!("must be at least 0 and smaller than node_addition_threshold ({})", p.node_addition_threshold),
+        ),
+        ("output_file_path", !p.output_file_path.is_empty(), String::from("can't be empty")),
+        ("remesh_every", p.remesh_every.is_none_or(|n| n > 0), String::from("must be positive")),
+        ("remesh_spacing", p.remesh_spacing.is_none_or(|s| s > 0.0), String::from("must be positive")),
+        ("export_num_points", p.export_num_points.is_none_or(|n| n >= 3), String::from("must be at least 3")),
+        ("snapshot_every", p.snapshot_every > 0, String::from("must be positive")),
+    ];
+    if let Some(a) = &p.adaptive_refinement {
+        checks.push(("adaptive_refinement.curvature_weight", a.curvature_weight >= 0.0, String::from("can't be negative")));
+        checks.push(("adaptive_refinement.proximity_weight", a.proximity_weight >= 0.0, String::from("can't be negative")));
+        checks.push((
+            "adaptive_refinement.min_scale",
+            a.min_scale > 0.0 && a.min_scale <= 1.0,
+            String::from("must be in (0, 1]"),
+        ));
+        checks.push(("adaptive_refinement.max_scale", a.max_scale >= 1.0, String::from("must be at least 1")));
+        checks.push((
+            "adaptive_refinement.node_budget",
+            a.node_budget >= 2 * p.initial_num_points,
+            format!("must leave room for the initial surface ({} nodes)", 2 * p.initial_num_points),
+        ));
+    }
+    if let Some(s) = &p.separation {
+        checks.push((
+            "separation.min_distance",
+            s.min_distance > 0.0 && s.min_distance < p.initial_thickness,
+            format!("must be positive and smaller than initial_thickness ({}), or the initial surface breaks it", p.initial_thickness),
+        ));
+        checks.push(("separation.penalty", s.penalty.is_none_or(|w| w >= 0.0), String::from("can't be negative")));
+    }
+    if let Some(h) = &p.heat {
+        checks.push(("helmholtz.grid_size", h.raster.width >= 3, String::from("must be at least 3")));
+        checks.push(("helmholtz.padding", h.raster.padding >= 0.0 && h.raster.padding < 0.5, String::from("must be in [0, 0.5)")));
+        checks.push(("helmholtz.alpha", h.alpha > 0.0, String::from("must be positive")));
+        checks.push((
+            "helmholtz.dt",
+            h.dt > 0.0 && h.alpha * h.dt <= 0.25,
+            format!("must be positive and at most 0.25 / alpha ({}), or the explicit scheme is unstable", 0.25 / h.alpha),
+        ));
+        checks.push(("helmholtz.iterations", h.iterations > 0, String::from("must be positive")));
+        checks.push(("helmholtz.slice_every", h.slice_every > 0, String::from("must be positive")));
+        checks.push(("helmholtz.steady_state_tolerance", h.steady_state_tolerance > 0.0, String::from("must be positive")));
+    }
+    if let Some(s) = &p.svg {
+        checks.push(("svg.scale", s.scale > 0.0, String::from("must be positive")));
+        checks.push(("svg.margin", s.margin >= 0.0, String::from("can't be negative")));
+        checks.push(("svg.stroke_width", s.outer.stroke_width > 0.0, String::from("must be positive")));
+        checks.push(("svg.node_radius", s.node_radius > 0.0, String::from("must be positive")));
+    }
+    if let Some(a) = &p.animation {
+        // GIF sizes are 16 bit
+        let max_side = if a.format == AnimationFormat::Gif { u16::MAX as usize } else { u32::MAX as usize };
+        checks.push(("animation.every", a.every > 0, String::from("must be positive")));
+        checks.push(("animation.width", a.raster.width > 0 && a.raster.width <= max_side, format!("must be in [1, {}]", max_side)));
+        checks.push(("animation.height", a.raster.height > 0 && a.raster.height <= max_side, format!("must be in [1, {}]", max_side)));
+        checks.push(("animation.padding", a.raster.padding >= 0.0 && a.raster.padding < 0.5, String::from("must be in [0, 0.5)")));
+    }
+    if let Some(t) = &p.trajectory {
+        checks.push(("trajectory.every", t.every > 0, String::from("must be positive")));
+        checks.push(("trajectory.keyframe_every", t.keyframe_every > 0, String::from("must be positive")));
+    }
+    for (i, r) in p.pinned.iter().enumerate() {
+        let (key, ok, problem) = match r.range {
+            PinRange::Angles(from, to) => (
+                "angles",
+                (0.0..360.0).contains(&from) && (0.0..360.0).contains(&to),
+                String::from("must be in [0, 360)"),
+            ),
+            PinRange::Indices(first, last) => (
+                "indices",
+                first < p.initial_num_points && last < p.initial_num_points,
+                format!("must be smaller than initial_num_points ({})", p.initial_num_points),
+            ),
+        };
+        if !ok {
+            return Err(ParamsError {
+                key: format!("pinned[{}].{}", i, key),
+                problem,
+            });
+        }
+    }
+    match checks.into_iter().find(|(_, ok, _)| !ok) {
+        Some((key, _, problem)) => Err(ParamsError {
+            key: String::from(key),
+            problem,
+        }),
+        None => Ok(()),
+    }
+}
+
+pub fn params_to_toml(p: &Params) -> toml::Value {
+    let mut m = toml::value::Table::new();
+    let mut put = |k: &str, v: toml::Value| {
+        m.insert(String::from(k), v);
+    };
+    put("initial_thickness", toml::Value::Float(p.initial_thickness));
+    put("initial_radius", toml::Value::Float(p.initial_radius));
+    put("initial_num_points", toml::Value::Integer(p.initial_num_points as i64));
+    put("initial_temperature", toml::Value::Float(p.initial_temperature));
+    put("compression_factor", toml::Value::Float(p.compression_factor));
+    put("softness_factor", toml::Value::Float(p.softness_factor));
+    put("how_smooth", toml::Value::Integer(p.how_smooth as i64));
+    put("max_merge_steps_away", toml::Value::Integer(p.max_merge_steps_away as i64));
+    put("node_addition_threshold", toml::Value::Float(p.node_addition_threshold));
+    put("node_deletion_threshold", toml::Value::Float(p.node_deletion_threshold));
+    put(
+        "low_high",
+        toml::Value::Array(vec![toml::Value::Float(p.low_high.0), toml::Value::Float(p.low_high.1)]),
+    );
+    put(
+        "recorders",
+        toml::Value::Array(
+            p.recorders
+                .iter()
+                .map(|r| match p.recorder_cadences.get(r) {
+                    None => toml::Value::String(r.clone()),
+                    Some(c) => {
+                        let mut t = toml::value::Table::new();
+                        t.insert(String::from("name"), toml::Value::String(r.clone()));
+                        if let Some(n) = c.every {
+                            t.insert(String::from("every"), toml::Value::Integer(n as i64));
+                        }
+                        if let Some(n) = c.log_spaced {
+                            t.insert(String::from("log_spaced"), toml::Value::Integer(n as i64));
+                        }
+                        if !c.on.is_empty() {
+                            t.insert(String::from("on"), event_names(&c.on));
+                        }
+                        toml::Value::Table(t)
+                    }
+                })
+                .collect(),
+        ),
+    );
+    put("temperature_param", toml::Value::Float(p.temperature_param));
+    put("output_file_path", toml::Value::String(p.output_file_path.clone()));
+    if let Some(n) = p.remesh_every {
+        put("remesh_every", toml::Value::Integer(n as i64));
+    }
+    if let Some(s) = p.remesh_spacing {
+        put("remesh_spacing", toml::Value::Float(s));
+    }
+    if let Some(n) = p.export_num_points {
+        put("export_num_points", toml::Value::Integer(n as i64));
+    }
+    put("snapshot_every", toml::Value::Integer(p.snapshot_every as i64));
+    put(
+        "snapshot_layout",
+        toml::Value::String(String::from(match p.snapshot_layout {
+            SnapshotLayout::PerStep => "per_step",
+            SnapshotLayout::Appended => "appended",
+        })),
+    );
+    put("debug_checks", toml::Value::Boolean(p.debug_checks));
+    if let Some(s) = p.seed {
+        put("seed", seed_to_toml(s));
+    }
+    if let Some(a) = &p.adaptive_refinement {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("curvature_weight"), toml::Value::Float(a.curvature_weight));
+        t.insert(String::from("proximity_weight"), toml::Value::Float(a.proximity_weight));
+        t.insert(String::from("min_scale"), toml::Value::Float(a.min_scale));
+        t.insert(String::from("max_scale"), toml::Value::Float(a.max_scale));
+        t.insert(String::from("node_budget"), toml::Value::Integer(a.node_budget as i64));
+        put("adaptive_refinement", toml::Value::Table(t));
+    }
+    if let Some(sep) = &p.separation {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("min_distance"), toml::Value::Float(sep.min_distance));
+        if let Some(w) = sep.penalty {
+            t.insert(String::from("penalty"), toml::Value::Float(w));
+        }
+        put("separation", toml::Value::Table(t));
+    }
+    if !p.pinned.is_empty() {
+        let regions = p
+            .pinned
+            .iter()
+            .map(|r| {
+                let mut t = toml::value::Table::new();
+                let layer_names = r.layers.iter().map(|l| toml::Value::String(String::from(if *l == OUTER { "outer" } else { "inner" })));
+                t.insert(String::from("layers"), toml::Value::Array(layer_names.collect()));
+                match r.range {
+                    PinRange::Angles(from, to) => t.insert(String::from("angles"), toml::Value::Array(vec![toml::Value::Float(from), toml::Value::Float(to)])),
+                    PinRange::Indices(first, last) => t.insert(
+                        String::from("indices"),
+                        toml::Value::Array(vec![toml::Value::Integer(first as i64), toml::Value::Integer(last as i64)]),
+                    ),
+                };
+                toml::Value::Table(t)
+            })
+            .collect();
+        put("pinned", toml::Value::Array(regions));
+    }
+    if let Some(h) = &p.heat {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("grid_size"), toml::Value::Integer(h.raster.width as i64));
+        t.insert(String::from("padding"), toml::Value::Float(h.raster.padding));
+        t.insert(String::from("alpha"), toml::Value::Float(h.alpha));
+        t.insert(String::from("dt"), toml::Value::Float(h.dt));
+        t.insert(String::from("iterations"), toml::Value::Integer(h.iterations as i64));
+        t.insert(String::from("high_temperature"), toml::Value::Float(h.high_temperature));
+        t.insert(String::from("low_temperature"), toml::Value::Float(h.low_temperature));
+        t.insert(String::from("initial_temperature"), toml::Value::Float(h.initial_temperature));
+        t.insert(String::from("slice_every"), toml::Value::Integer(h.slice_every as i64));
+        t.insert(String::from("steady_state_tolerance"), toml::Value::Float(h.steady_state_tolerance));
+        put("helmholtz", toml::Value::Table(t));
+    }
+    if let Some(s) = &p.svg {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("scale"), toml::Value::Float(s.scale));
+        t.insert(String::from("margin"), toml::Value::Float(s.margin));
+        t.insert(String::from("gray_matter"), toml::Value::String(s.outer.fill.clone()));
+        t.insert(String::from("white_matter"), toml::Value::String(s.inner.fill.clone()));
+        t.insert(String::from("outer_stroke"), toml::Value::String(s.outer.stroke.clone()));
+        t.insert(String::from("inner_stroke"), toml::Value::String(s.inner.stroke.clone()));
+        t.insert(String::from("stroke_width"), toml::Value::Float(s.outer.stroke_width));
+        t.insert(String::from("hull"), toml::Value::Boolean(s.hull.is_some()));
+        t.insert(String::from("stitching"), toml::Value::Boolean(s.stitching.is_some()));
+        if let Some(st) = &s.stitching {
+            t.insert(String::from("stitching_colour"), toml::Value::String(st.stroke.clone()));
+        }
+        t.insert(String::from("node_radius"), toml::Value::Float(s.node_radius));
+        t.insert(
+            String::from("colour_by"),
+            toml::Value::Array(s.colour_by.iter().map(|f| toml::Value::String(f.clone())).collect()),
+        );
+        put("svg", toml::Value::Table(t));
+    }
+    if let Some(a) = &p.animation {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("every"), toml::Value::Integer(a.every as i64));
+        t.insert(String::from("width"), toml::Value::Integer(a.raster.width as i64));
+        t.insert(String::from("height"), toml::Value::Integer(a.raster.height as i64));
+        t.insert(String::from("padding"), toml::Value::Float(a.raster.padding));
+        if let Some((name, _)) = ANIMATION_FORMATS.iter().find(|(_, f)| *f == a.format) {
+            t.insert(String::from("format"), toml::Value::String(String::from(*name)));
+        }
+        t.insert(String::from("frame_delay_ms"), toml::Value::Integer(a.frame_delay_ms as i64));
+        put("animation", toml::Value::Table(t));
+    }
+    if let Some(tp) = &p.trajectory {
+        let mut t = toml::value::Table::new();
+        t.insert(String::from("every"), toml::Value::Integer(tp.every as i64));
+        t.insert(String::from("keyframe_every"), toml::Value::Integer(tp.keyframe_every as i64));
+        put("trajectory", toml::Value::Table(t));
+    }
+    toml::Value::Table(m)
+}
+
+// Every value the run used, defaults included, in a file that can be fed back in as parameters.toml
+pub fn write_params(p: &Params, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(file_path)?;
+    writeln!(file, "# initial_gray_matter_area = {} (derived from initial_radius and initial_thickness)", p.initial_gray_matter_area)?;
+    write!(file, "{}", toml::to_string(&params_to_toml(p))?)?;
+    Ok(())
+}
+
+// output.csv -> output.params.toml, next to the recorders' output
+pub fn effective_params_path(output_file_path: &str) -> String {
+    Path::new(output_file_path).with_extension("params.toml").to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> toml::Value {
+        s.parse::<toml::Value>().unwrap()
+    }
+
+    #[test]
+    fn missing_keys_take_defaults_and_integers_pass_as_floats() {
+        let p = params_from_toml(&parse("initial_radius = 2\nhow_smooth = 4.0")).unwrap();
+        assert_eq!(p.initial_radius, 2.0);
+        assert_eq!(p.how_smooth, 4);
+        assert_eq!(p.softness_factor, Params::default().softness_factor);
+        assert_eq!(p.initial_gray_matter_area, annulus_area(2.0, p.initial_thickness));
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let e = params_from_toml(&parse("softness_factor = \"soft\"")).unwrap_err();
+        assert_eq!(e.key, "softness_factor");
+        let e = params_from_toml(&parse("low_high = [0.2, -0.2]")).unwrap_err();
+        assert_eq!(e.key, "low_high");
+        let e = params_from_toml(&parse("initial_radius = 1.0\ninitial_thickness = 1.5")).unwrap_err();
+        assert_eq!(e.key, "initial_thickness");
+        let e = params_from_toml(&parse("[adaptive_refinement]\nmin_scale = \"small\"")).unwrap_err();
+        assert_eq!(e.key, "adaptive_refinement.min_scale");
+        let e = params_from_toml(&parse("[separation]\npenalty = 1.0")).unwrap_err();
+        assert_eq!(e.key, "separation.min_distance");
+        let e = params_from_toml(&parse("initial_thickness = 0.1\n[separation]\nmin_distance = 0.1")).unwrap_err();
+        assert_eq!(e.key, "separation.min_distance");
+        let e = params_from_toml(&parse("[[pinned]]\nangles = [0, 10]\n[[pinned]]\nlayers = [\"middle\"]\nindices = [0, 3]")).unwrap_err();
+        assert_eq!(e.key, "pinned[1].layers");
+        let e = params_from_toml(&parse("snapshot_layout = \"sometimes\"")).unwrap_err();
+        assert_eq!(e.key, "snapshot_layout");
+        let e = params_from_toml(&parse("recorders = [\"energy\", { name = \"D_box\", on = [\"sometimes\"] }]")).unwrap_err();
+        assert_eq!(e.key, "recorders[1].on");
+        let e = params_from_toml(&parse("recorders = [{ name = \"D_box\", evry = 10 }]")).unwrap_err();
+        assert_eq!(e.key, "recorders[0].evry");
+        let e = params_from_toml(&parse("recorders = [\"energy\", \"kappa\"]")).unwrap_err();
+        assert_eq!(e.key, "recorders[1]");
+        let e = params_from_toml(&parse("recorders = [{ name = \"kappa\", every = 10 }]")).unwrap_err();
+        assert_eq!(e.key, "recorders[0].name");
+    }
+
+    #[test]
+    fn helmholtz_values_are_checked() {
+        let h = params_from_toml(&parse("[helmholtz]\nalpha = 1")).unwrap().heat.unwrap();
+        assert_eq!(h.dt, 0.25);
+        for (toml, key) in [
+            ("[helmholtz]\ngrid_size = -3", "helmholtz.grid_size"),
+            ("[helmholtz]\ngrid_size = 2", "helmholtz.grid_size"),
+            ("[helmholtz]\nalpha = \"hot\"", "helmholtz.alpha"),
+            ("[helmholtz]\nalpha = 0", "helmholtz.alpha"),
+            ("[helmholtz]\nalpha = 2\ndt = 0.2", "helmholtz.dt"),
+            ("helmholtz = 3", "helmholtz"),
+        ] {
+            assert_eq!(params_from_toml(&parse(toml)).unwrap_err().key, key);
+        }
+    }
+
+    #[test]
+    fn svg_values_are_checked() {
+        let s = params_from_toml(&parse("[svg]\nstitching = true\nstitching_colour = \"teal\"\nouter_stroke = \"red\"")).unwrap().svg.unwrap();
+        assert_eq!(s.stitching.unwrap().stroke, "teal");
+        assert_eq!((s.outer.stroke.as_str(), s.inner.stroke.as_str()), ("red", "black"));
+        for (toml, key) in [
+            ("[svg]\ncolour_by = [\"thickness\", 3]", "svg.colour_by"),
+            ("[svg]\ncolour_by = [\"temperature\"]", "svg.colour_by"),
+            ("[svg]\nhull = \"no\"", "svg.hull"),
+            ("[svg]\nscale = -1", "svg.scale"),
+            ("[svg]\nstitching_colour = \"teal\"", "svg.stitching_colour"),
+        ] {
+            assert_eq!(params_from_toml(&parse(toml)).unwrap_err().key, key);
+        }
+    }
+
+    #[test]
+    fn animation_values_are_checked() {
+        let a = params_from_toml(&parse("[animation]\nformat = \"apng\"\nframe_delay_ms = 65535")).unwrap().animation.unwrap();
+        assert_eq!((a.format, a.frame_delay_ms), (AnimationFormat::Apng, 65535));
+        for (toml, key) in [
+            ("[animation]\nformat = \"mp4\"", "animation.format"),
+            ("[animation]\nframe_delay_ms = 70000", "animation.frame_delay_ms"),
+            ("[animation]\nwidth = 0", "animation.width"),
+            ("[animation]\nheight = -20", "animation.height"),
+            ("[animation]\nwidth = 70000", "animation.width"),
+            ("[animation]\nevery = 0", "animation.every"),
+            ("[trajectory]\nevery = 0", "trajectory.every"),
+            ("[trajectory]\nkeyframe_every = 2.5", "trajectory.keyframe_every"),
+        ] {
+            assert_eq!(params_from_toml(&parse(toml)).unwrap_err().key, key);
+        }
+        assert!(params_from_toml(&parse("[animation]\nformat = \"apng\"\nwidth = 70000")).is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_found() {
+        let t = parse("how_smoth = 3\nhow_smooth = 3\n[helmholtz]\niterations = 10\ngrid_sise = 64");
+        assert_eq!(unknown_keys(&t), vec![String::from("how_smoth"), String::from("helmholtz.grid_sise")]);
+        let t = parse(
+            "[separation]\nmin_distnace = 0.01\n[adaptive_refinement]\nnode_budget = 10\ncurvature = 1\n\
+             [[pinned]]\nangles = [0, 10]\n[[pinned]]\nindices = [0, 5]\nlayer = [\"inner\"]",
+        );
+        assert_eq!(unknown_keys(&t), vec!["adaptive_refinement.curvature", "separation.min_distnace", "pinned[1].layer"]);
+    }
+
+    #[test]
+    fn written_params_read_back_the_same() {
+        let p = Params {
+            recorders: vec![String::from("energy"), String::from("D_box")],
+            recorder_cadences: vec![(
+                String::from("D_box"),
+                Cadence {
+                    every: Some(1000),
+                    log_spaced: None,
+                    on: vec![RecordEvent::NewMinimum, RecordEvent::End],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            remesh_every: Some(100),
+            seed: Some(42),
+            snapshot_every: 250,
+            snapshot_layout: SnapshotLayout::PerStep,
+            adaptive_refinement: Some(AdaptiveRefinement::default()),
+            separation: Some(Separation {
+                min_distance: 0.01,
+                penalty: Some(5.0),
+            }),
+            pinned: vec![
+                PinnedRegion::both_layers(PinRange::Angles(250.0, 290.0)),
+                PinnedRegion {
+                    layers: vec![INNER],
+                    range: PinRange::Indices(4990, 9),
+                },
+            ],
+            heat: Some(HeatParams { dt: 0.1, ..HeatParams::default() }),
+            svg: Some(SvgParams {
+                stitching: Some(LayerStyle::line(STITCHING_COLOUR, 0.5)),
+                hull: None,
+                colour_by: vec![String::from("thickness")],
+                ..SvgParams::default()
+            }),
+            animation: Some(AnimationParams {
+                format: AnimationFormat::Apng,
+                ..AnimationParams::default()
+            }),
+            trajectory: Some(TrajectoryParams {
+                every: 10,
+                keyframe_every: 500,
+            }),
+            ..Params::default()
+        };
+        let back = params_from_toml(&params_to_toml(&p)).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", p));
+        assert!(unknown_keys(&params_to_toml(&p)).is_empty());
+    }
+
+    #[test]
+    fn seeds_past_i64_round_trip() {
+        for seed in [0, i64::MAX as u64, i64::MAX as u64 + 1, u64::MAX] {
+            let p = Params { seed: Some(seed), ..Params::default() };
+            let text = toml::to_string(&params_to_toml(&p)).unwrap();
+            assert_eq!(params_from_toml(&parse(&text)).unwrap().seed, Some(seed));
+        }
+        assert_eq!(params_from_toml(&parse("seed = \"18446744073709551615\"")).unwrap().seed, Some(u64::MAX));
+        assert_eq!(params_from_toml(&parse("seed = \"-1\"")).unwrap_err().key, "seed");
+    }
+}
@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use csv::Reader;
+
+use graph::cyclic_graph_from_coords;
+use graph::types::ThickSurface;
+use graph::validate::containment;
+
+// Reads a CSV of x,y coordinates with a header row, such as the dados_*.csv files or the box_counting/*/out.csv inputs.
+// Only the first two columns are used.
+pub fn read_points_csv(file_path: &str) -> Result<Vec<(f64, f64)>, Box<dyn Error>> {
+    let mut reader = Reader::from_path(file_path)?;
+    let mut points = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        if record.len() < 2 {
+            return Err(From::from(format!("{}: expected at least 2 columns, found {}", file_path, record.len())));
+        }
+        points.push((record[0].trim().parse::<f64>()?, record[1].trim().parse::<f64>()?));
+    }
+    Ok(points)
+}
+
+// An outer and an inner layer, such as a run's dados_out.csv and dados_in.csv, refusing surfaces that aren't a proper annulus
+pub fn read_thick_surface(outer_path: &str, inner_path: &str) -> Result<ThickSurface, Box<dyn Error>> {
+    let (outer, inner) = (read_points_csv(outer_path)?, read_points_csv(inner_path)?);
+    if outer.len() < 3 || inner.len() < 3 {
+        return Err(From::from(format!("{} and {}: each layer needs at least 3 points", outer_path, inner_path)));
+    }
+    let ts = ThickSurface::new(cyclic_graph_from_coords(&outer), cyclic_graph_from_coords(&inner));
+    match containment(&ts) {
+        Some(v) => Err(From::from(format!("{} and {} don't make a proper surface: {}", outer_path, inner_path, v))),
+        None => Ok(ts),
+    }
+}
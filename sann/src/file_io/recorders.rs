@@ -1,51 +1,285 @@
+use analysis::box_counting;
+use analysis::thickness;
 use graph;
 use simulated_annealing;
 
 use std::fs::File;
+use std::io;
 use std::io::Write;
 
 use graph::types::{ThickSurface, INNER, OUTER};
 use simulated_annealing::SimState;
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use types::Params;
 use graph::convex_hull::convex_hull_from_graph;
 
 type RecorderFn = for<'r, 's> fn(&'r ThickSurface, &'s Params) -> f64;
 
+/* Something that writes one or more columns of the recorders' CSV. A recorder lives as long as the run, so it can keep
+   whatever history it needs (running means, windows, the previous value...) between rows. */
+pub trait Recorder {
+    // Headers of its columns, in the order record returns them
+    fn columns(&self) -> Vec<String>;
+    // The row for the state the simulation is in now. Called at most once per timestep
+    fn record(&mut self, sim_state: &SimState, p: &Params) -> Vec<f64>;
+    // Called instead of record on the steps its cadence skips, for recorders that need to see every step
+    fn observe(&mut self, _sim_state: &SimState, _p: &Params) {}
+}
+
+// The stateless metrics below: one column, computed from the surface alone
+struct FnRecorder {
+    name: String,
+    f: RecorderFn,
+}
+
+impl Recorder for FnRecorder {
+    fn columns(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+    fn record(&mut self, sim_state: &SimState, p: &Params) -> Vec<f64> {
+        vec![(self.f)(&sim_state.ts, p)]
+    }
+}
+
+// Mean and variance of a metric over every recorded step so far (Welford's)
+pub struct RunningStats {
+    name: String,
+    f: RecorderFn,
+    n: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    pub fn of(name: &str, f: RecorderFn) -> RunningStats {
+        RunningStats {
+            name: String::from(name),
+            f,
+            n: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    // Population variance, 0 until there are two values
+    pub fn variance(&self) -> f64 {
+        if self.n < 2 {
+            0.0
+        } else {
+            self.m2 / self.n as f64
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+}
+
+impl Recorder for RunningStats {
+    fn columns(&self) -> Vec<String> {
+        vec![format!("{} mean", self.name), format!("{} variance", self.name)]
+    }
+    fn record(&mut self, sim_state: &SimState, p: &Params) -> Vec<f64> {
+        self.add((self.f)(&sim_state.ts, p));
+        vec![self.mean(), self.variance()]
+    }
+}
+
+// Fraction of the last `window` steps whose move was accepted, recorded or not
+struct AcceptanceRate {
+    window: usize,
+    outcomes: VecDeque<bool>,
+}
+
+impl Recorder for AcceptanceRate {
+    fn columns(&self) -> Vec<String> {
+        vec![String::from("acceptance rate")]
+    }
+    fn record(&mut self, sim_state: &SimState, p: &Params) -> Vec<f64> {
+        self.observe(sim_state, p);
+        if self.outcomes.is_empty() {
+            return vec![f64::NAN];
+        }
+        vec![self.outcomes.iter().filter(|a| **a).count() as f64 / self.outcomes.len() as f64]
+    }
+    fn observe(&mut self, sim_state: &SimState, _p: &Params) {
+        if let Some(o) = &sim_state.last_step {
+            self.outcomes.push_back(o.accepted);
+            if self.outcomes.len() > self.window {
+                self.outcomes.pop_front();
+            }
+        }
+    }
+}
+
+// Change of a metric per step since the previous row, NaN on the first one
+struct Derivative {
+    name: String,
+    f: RecorderFn,
+    previous: Option<(u64, f64)>,
+}
+
+impl Recorder for Derivative {
+    fn columns(&self) -> Vec<String> {
+        vec![format!("d({})/dt", self.name)]
+    }
+    fn record(&mut self, sim_state: &SimState, p: &Params) -> Vec<f64> {
+        let x = (self.f)(&sim_state.ts, p);
+        let ret = match self.previous {
+            Some((t, previous)) if sim_state.timestep > t => (x - previous) / (sim_state.timestep - t) as f64,
+            _ => f64::NAN,
+        };
+        self.previous = Some((sim_state.timestep, x));
+        vec![ret]
+    }
+}
+
+// The simulation's own state rather than the surface's
+struct StepState;
+
+impl Recorder for StepState {
+    fn columns(&self) -> Vec<String> {
+        vec![String::from("temperature"), String::from("proposed energy"), String::from("accepted")]
+    }
+    fn record(&mut self, sim_state: &SimState, _p: &Params) -> Vec<f64> {
+        match &sim_state.last_step {
+            Some(o) => vec![sim_state.temperature, o.energy_proposed, if o.accepted { 1.0 } else { 0.0 }],
+            None => vec![sim_state.temperature, f64::NAN, f64::NAN],
+        }
+    }
+}
+
+// All of thickness::stats from one nearest-points pass, instead of one pass per "thickness ..." recorder
+struct ThicknessStats;
+
+impl Recorder for ThicknessStats {
+    fn columns(&self) -> Vec<String> {
+        ["mean", "sd", "min", "max"].iter().map(|c| format!("thickness {}", c)).collect()
+    }
+    fn record(&mut self, sim_state: &SimState, _p: &Params) -> Vec<f64> {
+        let s = thickness::stats(&thickness::from_nearest_points(&sim_state.ts));
+        vec![s.mean, s.sd, s.min, s.max]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordEvent {
+    // The energy is the lowest it's been in the run
+    NewMinimum,
+    // The step's move was accepted
+    Accepted,
+    // The last step of the run (see record_end)
+    End,
+}
+
+// How events are written in parameters.toml
+pub const EVENT_NAMES: [(&str, RecordEvent); 3] = [
+    ("new_minimum", RecordEvent::NewMinimum),
+    ("accepted", RecordEvent::Accepted),
+    ("end", RecordEvent::End),
+];
+
+// When a recorder writes its columns: on any timestep that matches one of these
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cadence {
+    pub every: Option<u64>,
+    // About this many rows per decade of timesteps, e.g. with 10: 1, 2, 3, 4, 6, 7, 8, 10, 13, 16, 20, 26, 32...
+    pub log_spaced: Option<u64>,
+    pub on: Vec<RecordEvent>,
+}
+
+impl Cadence {
+    pub fn every(n: u64) -> Cadence {
+        Cadence {
+            every: Some(n),
+            log_spaced: None,
+            on: vec![],
+        }
+    }
+
+    pub fn is_due(&self, timestep: u64, events: &[RecordEvent]) -> bool {
+        self.every.is_some_and(|n| timestep.is_multiple_of(n))
+            || self.log_spaced.is_some_and(|n| log_spaced_due(timestep, n))
+            || self.on.iter().any(|e| events.contains(e))
+    }
+}
+
+// Whether timestep is the first one past a boundary 10^(k / per_decade)
+fn log_spaced_due(timestep: u64, per_decade: u64) -> bool {
+    if timestep == 0 {
+        return false;
+    }
+    let bucket = |t: u64| ((t as f64).log10() * per_decade as f64).floor() as i64;
+    timestep == 1 || bucket(timestep) > bucket(timestep - 1)
+}
+
 pub struct RecordingState {
     pub f: File,
+    pub recorders: Vec<Box<dyn Recorder>>,
+    // One per recorder
+    cadences: Vec<Cadence>,
+    // The latest value of every column, NaN for the ones not recorded yet
     pub last_recorded: Vec<f64>,
+    // Recording twice in a step does nothing the second time, so the recorders never see a step twice
+    last_timestep: Option<u64>,
+    lowest_energy: f64,
 }
 
 impl RecordingState {
-    pub fn empty_state(file_path: &str) -> Option<RecordingState> {
-        return match File::create(file_path) {
-            Ok(f) => Some(RecordingState { f, last_recorded: vec![] }),
-            Err(e) => panic!("Couldn't write to file: {:?}", e),
-        };
+    pub fn empty_state(file_path: &str) -> io::Result<RecordingState> {
+        Ok(RecordingState {
+            f: File::create(file_path)?,
+            recorders: vec![],
+            cadences: vec![],
+            last_recorded: vec![],
+            last_timestep: None,
+            lowest_energy: f64::INFINITY,
+        })
     }
-    pub fn initial_state(p: &Params) -> Option<RecordingState> {
-        if !p.recorders.is_empty() {
-            let mut header = String::new();
-            header.push_str("timestep");
-            for r in &p.recorders {
-                header.push_str(",");
-                header.push_str(r);
-            }
-            header.push_str("\n");
-
-            return match File::create(&p.output_file_path) {
-                Ok(mut f) => match f.write_all(header.as_bytes()) {
-                    Ok(_) => Some(RecordingState {
-                        f,
-                        last_recorded: Vec::new(),
-                    }),
-                    Err(e) => panic!("Couldn't write to file: {:?}", e),
-                },
-                Err(_) => None,
-            };
+
+    // With no recorders the file only gets its header, and nothing is ever recorded. Recorders given by name alone, with
+    // no cadence of their own in p.recorder_cadences, record on default_cadence, which is up to the mode running
+    pub fn initial_state(p: &Params, default_cadence: &Cadence) -> io::Result<RecordingState> {
+        let recorders: Vec<Box<dyn Recorder>> = p
+            .recorders
+            .iter()
+            .map(|r| match recorder_by_name(r) {
+                Some(recorder) => recorder,
+                None => panic!("unsupported recorder: {}", r),
+            })
+            .collect();
+        let cadences = p.recorders.iter().map(|r| p.recorder_cadences.get(r).unwrap_or(default_cadence).clone()).collect();
+        let num_columns = recorders.iter().map(|r| r.columns().len()).sum();
+        let mut header = String::new();
+        header.push_str("timestep");
+        for c in recorders.iter().flat_map(|r| r.columns()) {
+            header.push(',');
+            header.push_str(&c);
         }
-        None
+        header.push('\n');
+
+        let mut f = File::create(&p.output_file_path)?;
+        f.write_all(header.as_bytes())?;
+        Ok(RecordingState {
+            f,
+            recorders,
+            cadences,
+            last_recorded: vec![f64::NAN; num_columns],
+            last_timestep: None,
+            lowest_energy: f64::INFINITY,
+        })
+    }
+
+    // Every column after timestep, in the order of last_recorded
+    pub fn columns(&self) -> Vec<String> {
+        self.recorders.iter().flat_map(|r| r.columns()).collect()
     }
 }
 
@@ -104,87 +338,493 @@ pub fn k (ts: &ThickSurface, _p: &Params) -> f64 {
    0.5 * (graph::gray_matter_area(ts)/graph::perimeter(&ts.layers[OUTER])).log10() +  graph::perimeter(&ts.layers[OUTER]).log10() - 1.5 * graph::perimeter( &convex_hull_from_graph( &ts.layers[OUTER] ) )
 }
 
+fn box_counting_dimension(ts: &ThickSurface, _p: &Params) -> f64 {
+    box_counting::fractal_dimension(&ts.layers[OUTER], &box_counting::default_epsilons()).dimension
+}
 
-fn name_to_fn(n: &str) -> Option<RecorderFn> {
-    match n {
-        "energy" => Some(energy),
-        "P_ext" => Some(log_outer_perimeter),
-        "P_con" => Some(log_convex_perimeter),
-        "T" => Some(log_thickness),
-        "outer perimeter" => Some(outer_perimeter),
-        "inner perimeter" => Some(inner_perimeter),
-        "outer area" => Some(outer_area),
-        "inner area" => Some(inner_area),
-        "gray matter area" => Some(gray_matter_area),
-        "num inner points" => Some(num_inner_points),
-        "num outer points" => Some(num_outer_points),
-        "convex area" => Some(convex_area),
-        "convex perimeter" => Some(convex_perimeter),
-        "convex gray area" => Some(convex_gray_area),
-        _ => None,
-    }
-}
-
-pub fn rec_map() -> HashMap<String, RecorderFn> {
-    let mut r = HashMap::new();
-    r.insert(
-        String::from("energy"),
-        energy as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("outer perimeter"),
-        outer_perimeter as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("inner perimeter"),
-        inner_perimeter as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("outer area"),
-        outer_area as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("inner area"),
-        inner_area as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("gray matter area"),
-        gray_matter_area as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("num inner points"),
-        num_inner_points as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r.insert(
-        String::from("num outer points"),
-        num_outer_points as for<'r, 's> fn(&'r graph::types::ThickSurface, &'s Params) -> f64,
-    );
-    r
+fn box_counting_r_squared(ts: &ThickSurface, _p: &Params) -> f64 {
+    box_counting::fractal_dimension(&ts.layers[OUTER], &box_counting::default_epsilons()).r_squared
 }
 
-pub fn record(sim_state: &SimState, p: &Params, recording_state: &mut RecordingState) {
-    let mut line = String::new();
-    let mut new_vals = Vec::new();
-    for r in &p.recorders {
-        let val = match name_to_fn(r) {
-            Some(recorder) => recorder(&sim_state.ts, p),
-            None => panic!("unsupported recorder: {}", r),
-        };
-        new_vals.push(val);
-        line.push_str(format!(",{}", val).as_str());
+fn thickness_mean(ts: &ThickSurface, _p: &Params) -> f64 {
+    thickness::stats(&thickness::from_nearest_points(ts)).mean
+}
+
+fn thickness_sd(ts: &ThickSurface, _p: &Params) -> f64 {
+    thickness::stats(&thickness::from_nearest_points(ts)).sd
+}
+
+fn thickness_min(ts: &ThickSurface, _p: &Params) -> f64 {
+    thickness::stats(&thickness::from_nearest_points(ts)).min
+}
+
+fn thickness_max(ts: &ThickSurface, _p: &Params) -> f64 {
+    thickness::stats(&thickness::from_nearest_points(ts)).max
+}
+
+// Roughly what a row of a recorder costs, to pick its cadence by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cost {
+    // A pass over the nodes or less
+    Cheap,
+    // A convex hull
+    Moderate,
+    // Every outer node against every inner edge (thickness), or box counting at every box size
+    Expensive,
+}
+
+impl Cost {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cost::Cheap => "cheap",
+            Cost::Moderate => "moderate",
+            Cost::Expensive => "expensive",
+        }
+    }
+}
+
+enum RecorderKind {
+    // One column, computed from the surface alone
+    Metric(RecorderFn),
+    // Its own columns and history
+    Stateful(fn() -> Box<dyn Recorder>),
+}
+
+// What list-recorders and the GUI show of a recorder, and how to make one
+pub struct RecorderInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    // Of each column, in order
+    pub units: &'static str,
+    pub cost: Cost,
+    kind: RecorderKind,
+}
+
+impl RecorderInfo {
+    pub fn build(&self) -> Box<dyn Recorder> {
+        match self.kind {
+            RecorderKind::Metric(f) => Box::new(FnRecorder {
+                name: String::from(self.name),
+                f,
+            }),
+            RecorderKind::Stateful(make) => make(),
+        }
+    }
+}
+
+// Steps the acceptance rate is taken over
+const ACCEPTANCE_WINDOW: usize = 1000;
+
+// Every recorder `recorders` can name. Params naming anything else are rejected when they're read
+pub static REGISTRY: &[RecorderInfo] = &[
+    RecorderInfo {
+        name: "energy",
+        description: "what the annealing minimises: white matter area plus the squared stretch of the gray matter",
+        units: "area",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(energy),
+    },
+    RecorderInfo {
+        name: "P_ext",
+        description: "log10 of the outer perimeter",
+        units: "log10 length",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(log_outer_perimeter),
+    },
+    RecorderInfo {
+        name: "P_con",
+        description: "log10 of the perimeter of the outer layer's convex hull",
+        units: "log10 length",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Metric(log_convex_perimeter),
+    },
+    RecorderInfo {
+        name: "T",
+        description: "log10 of the average thickness, gray matter area over outer perimeter",
+        units: "log10 length",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(log_thickness),
+    },
+    RecorderInfo {
+        name: "k",
+        description: "0.5 T + P_ext - 1.5 times the convex hull's perimeter, which settles as the surface does",
+        units: "dimensionless",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Metric(k),
+    },
+    RecorderInfo {
+        name: "outer perimeter",
+        description: "length of the outer layer",
+        units: "length",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(outer_perimeter),
+    },
+    RecorderInfo {
+        name: "inner perimeter",
+        description: "length of the inner layer",
+        units: "length",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(inner_perimeter),
+    },
+    RecorderInfo {
+        name: "outer area",
+        description: "area inside the outer layer",
+        units: "area",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(outer_area),
+    },
+    RecorderInfo {
+        name: "inner area",
+        description: "area inside the inner layer (white matter)",
+        units: "area",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(inner_area),
+    },
+    RecorderInfo {
+        name: "gray matter area",
+        description: "area between the layers",
+        units: "area",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(gray_matter_area),
+    },
+    RecorderInfo {
+        name: "num inner points",
+        description: "nodes in the inner layer",
+        units: "nodes",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(num_inner_points),
+    },
+    RecorderInfo {
+        name: "num outer points",
+        description: "nodes in the outer layer",
+        units: "nodes",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Metric(num_outer_points),
+    },
+    RecorderInfo {
+        name: "convex area",
+        description: "area of the outer layer's convex hull",
+        units: "area",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Metric(convex_area),
+    },
+    RecorderInfo {
+        name: "convex perimeter",
+        description: "perimeter of the outer layer's convex hull",
+        units: "length",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Metric(convex_perimeter),
+    },
+    RecorderInfo {
+        name: "convex gray area",
+        description: "area between the outer layer's convex hull and the inner layer",
+        units: "area",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Metric(convex_gray_area),
+    },
+    RecorderInfo {
+        name: "D_box",
+        description: "box-counting fractal dimension of the outer layer",
+        units: "dimensionless",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(box_counting_dimension),
+    },
+    RecorderInfo {
+        name: "D_box R2",
+        description: "r squared of the fit D_box comes from",
+        units: "dimensionless",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(box_counting_r_squared),
+    },
+    RecorderInfo {
+        name: "thickness mean",
+        description: "mean distance from the outer nodes to the inner layer",
+        units: "length",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(thickness_mean),
+    },
+    RecorderInfo {
+        name: "thickness sd",
+        description: "standard deviation of the distances from the outer nodes to the inner layer",
+        units: "length",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(thickness_sd),
+    },
+    RecorderInfo {
+        name: "thickness min",
+        description: "shortest distance from an outer node to the inner layer",
+        units: "length",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(thickness_min),
+    },
+    RecorderInfo {
+        name: "thickness max",
+        description: "longest distance from an outer node to the inner layer",
+        units: "length",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Metric(thickness_max),
+    },
+    RecorderInfo {
+        name: "thickness",
+        description: "thickness mean, sd, min and max in one pass",
+        units: "length",
+        cost: Cost::Expensive,
+        kind: RecorderKind::Stateful(|| Box::new(ThicknessStats)),
+    },
+    RecorderInfo {
+        name: "energy stats",
+        description: "running mean and variance of the energy over the recorded steps",
+        units: "area, area^2",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Stateful(|| Box::new(RunningStats::of("energy", energy))),
+    },
+    RecorderInfo {
+        name: "k stats",
+        description: "running mean and variance of k over the recorded steps",
+        units: "dimensionless",
+        cost: Cost::Moderate,
+        kind: RecorderKind::Stateful(|| Box::new(RunningStats::of("k", k))),
+    },
+    RecorderInfo {
+        name: "energy rate",
+        description: "change of the energy per step since the previous row",
+        units: "area per step",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Stateful(|| {
+            Box::new(Derivative {
+                name: String::from("energy"),
+                f: energy,
+                previous: None,
+            })
+        }),
+    },
+    RecorderInfo {
+        name: "acceptance rate",
+        description: "fraction of the last 1000 steps whose move was accepted",
+        units: "fraction",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Stateful(|| {
+            Box::new(AcceptanceRate {
+                window: ACCEPTANCE_WINDOW,
+                outcomes: VecDeque::new(),
+            })
+        }),
+    },
+    RecorderInfo {
+        name: "step",
+        description: "temperature, energy of the proposed move and whether it was accepted (1 or 0)",
+        units: "temperature, area, boolean",
+        cost: Cost::Cheap,
+        kind: RecorderKind::Stateful(|| Box::new(StepState)),
+    },
+];
+
+pub fn recorder_info(n: &str) -> Option<&'static RecorderInfo> {
+    REGISTRY.iter().find(|r| r.name == n)
+}
+
+pub fn recorder_by_name(n: &str) -> Option<Box<dyn Recorder>> {
+    recorder_info(n).map(|r| r.build())
+}
+
+// A row with the columns of the recorders that are due at this step, the others left empty. Nothing is written when
+// none of them are
+fn record_row(sim_state: &SimState, p: &Params, recording_state: &mut RecordingState, end: bool) {
+    if recording_state.last_timestep == Some(sim_state.timestep) {
+        return;
+    }
+    recording_state.last_timestep = Some(sim_state.timestep);
+
+    let mut events = Vec::new();
+    if end {
+        events.push(RecordEvent::End);
+    }
+    if sim_state.last_step.is_some_and(|o| o.accepted) {
+        events.push(RecordEvent::Accepted);
+    }
+    if recording_state.cadences.iter().any(|c| c.on.contains(&RecordEvent::NewMinimum)) {
+        let e = energy(&sim_state.ts, p);
+        if e < recording_state.lowest_energy {
+            recording_state.lowest_energy = e;
+            events.push(RecordEvent::NewMinimum);
+        }
+    }
+
+    let mut line = format!("{}", sim_state.timestep);
+    let mut any_due = false;
+    let mut column = 0;
+    for (r, c) in recording_state.recorders.iter_mut().zip(recording_state.cadences.iter()) {
+        let width = r.columns().len();
+        if c.is_due(sim_state.timestep, &events) {
+            any_due = true;
+            for (i, val) in r.record(sim_state, p).into_iter().enumerate() {
+                line.push_str(format!(",{}", val).as_str());
+                recording_state.last_recorded[column + i] = val;
+            }
+        } else {
+            r.observe(sim_state, p);
+            line.push_str(&",".repeat(width));
+        }
+        column += width;
     }
     line.push_str("\n");
-    if line.len() > 0 {
-        line.remove(0);
-    }; // remove leading comma
-    if true
-    /* new_vals != recording_state.last_recorded */
-    {
-        line.insert_str(0, &*format!("{},", sim_state.timestep));
+    if any_due {
         match recording_state.f.write_all(line.as_bytes()) {
             Ok(_) => {}
             Err(e) => panic!("Couldn't write to file: {:?}", e),
         }
-        recording_state.last_recorded = new_vals;
+    }
+}
+
+// Meant to be called after every step: each recorder's cadence decides whether it writes anything
+pub fn record(sim_state: &SimState, p: &Params, recording_state: &mut RecordingState) {
+    record_row(sim_state, p, recording_state, false)
+}
+
+// Instead of record on the run's last step, so recorders with on = ["end"] get their row
+pub fn record_end(sim_state: &SimState, p: &Params, recording_state: &mut RecordingState) {
+    record_row(sim_state, p, recording_state, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simulated_annealing::StepOutcome;
+    use std::env::temp_dir;
+
+    #[test]
+    fn recorders_keep_state_between_rows() {
+        let mut stats = RunningStats::of("x", energy);
+        for x in [1.0, 2.0, 3.0, 4.0] {
+            stats.add(x);
+        }
+        assert_eq!((stats.mean(), stats.variance()), (2.5, 1.25));
+
+        let path = temp_dir().join("sann_recorders_test.csv");
+        let p = Params {
+            initial_num_points: 40,
+            recorders: vec![String::from("energy"), String::from("thickness"), String::from("acceptance rate"), String::from("energy rate")],
+            output_file_path: String::from(path.to_str().unwrap()),
+            ..Params::default()
+        };
+        let mut s = SimState::initial_state(&p);
+        let mut rs = RecordingState::initial_state(&p, &Cadence::every(1)).unwrap();
+        assert_eq!(rs.columns().len(), 7);
+
+        let outcome = |accepted| StepOutcome {
+            accepted,
+            energy_before: 1.0,
+            energy_proposed: 1.0,
+        };
+        s.last_step = Some(outcome(true));
+        s.timestep = 1;
+        record(&s, &p, &mut rs);
+        // Same step again: no new row, and the window still holds a single step
+        record(&s, &p, &mut rs);
+        s.last_step = Some(outcome(false));
+        s.timestep = 3;
+        record(&s, &p, &mut rs);
+        assert_eq!(rs.last_recorded[5], 0.5);
+        assert_eq!(rs.last_recorded[6], 0.0);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[0], "timestep,energy,thickness mean,thickness sd,thickness min,thickness max,acceptance rate,d(energy)/dt");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with(",1,NaN"));
+    }
+
+    #[test]
+    fn registry_builds_every_recorder_once() {
+        for (i, r) in REGISTRY.iter().enumerate() {
+            assert!(REGISTRY[..i].iter().all(|o| o.name != r.name), "{} is registered twice", r.name);
+            assert!(!r.description.is_empty() && !r.units.is_empty());
+            let columns = r.build().columns();
+            assert!(!columns.is_empty());
+            if let RecorderKind::Metric(_) = r.kind {
+                assert_eq!(columns, vec![String::from(r.name)]);
+            }
+        }
+        assert_eq!(recorder_info("k").unwrap().cost, Cost::Moderate);
+        assert!(recorder_by_name("P_ext").is_some() && recorder_by_name("kappa").is_none());
+    }
+
+    #[test]
+    fn cadences_pick_their_steps() {
+        let log = Cadence {
+            every: None,
+            log_spaced: Some(10),
+            on: vec![],
+        };
+        let due: Vec<u64> = (0..200).filter(|t| log.is_due(*t, &[])).collect();
+        assert_eq!(due, vec![1, 2, 3, 4, 6, 7, 8, 10, 13, 16, 20, 26, 32, 40, 51, 64, 80, 100, 126, 159]);
+        let sparse = Cadence {
+            every: Some(100),
+            log_spaced: None,
+            on: vec![RecordEvent::End],
+        };
+        assert!(sparse.is_due(300, &[]) && !sparse.is_due(301, &[RecordEvent::Accepted]) && sparse.is_due(301, &[RecordEvent::End]));
+
+        // Rows only when something is due, with the columns of the others left empty
+        let path = temp_dir().join("sann_cadences_test.csv");
+        let p = Params {
+            initial_num_points: 40,
+            recorders: vec![String::from("num outer points"), String::from("energy")],
+            recorder_cadences: vec![(String::from("energy"), sparse.clone())].into_iter().collect(),
+            output_file_path: String::from(path.to_str().unwrap()),
+            ..Params::default()
+        };
+        let mut s = SimState::initial_state(&p);
+        let run_150_steps = |s: &mut SimState, rs: &mut RecordingState| {
+            for t in 1..=150 {
+                s.timestep = t;
+                if t == 150 {
+                    record_end(s, &p, rs);
+                } else {
+                    record(s, &p, rs);
+                }
+            }
+        };
+        let mut rs = RecordingState::initial_state(&p, &Cadence::every(1)).unwrap();
+        run_150_steps(&mut s, &mut rs);
+        let written = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 151);
+        assert_eq!(lines[1], "1,40,");
+        assert!(lines[100].starts_with("100,40,") && lines[100].len() > "100,40,".len());
+        assert!(lines[150].starts_with("150,40,") && !lines[150].ends_with(","));
+
+        // A recorder given by name alone takes the mode's cadence instead of recording every step
+        let mut rs = RecordingState::initial_state(&p, &sparse).unwrap();
+        run_150_steps(&mut s, &mut rs);
+        let written = std::fs::read_to_string(&path).unwrap();
+        let steps: Vec<&str> = written.lines().skip(1).map(|l| l.split(',').next().unwrap()).collect();
+        assert_eq!(steps, vec!["100", "150"]);
+
+        // The steps a recorder's cadence skips still count towards its acceptance rate
+        let mut rate = recorder_by_name("acceptance rate").unwrap();
+        s.last_step = Some(StepOutcome { accepted: false, energy_before: 1.0, energy_proposed: 2.0 });
+        rate.observe(&s, &p);
+        s.last_step = Some(StepOutcome { accepted: true, energy_before: 1.0, energy_proposed: 0.5 });
+        assert_eq!(rate.record(&s, &p), vec![0.5]);
+    }
+
+    #[test]
+    fn no_recorders_still_make_a_state() {
+        let path = temp_dir().join("sann_no_recorders_test.csv");
+        let p = Params {
+            initial_num_points: 40,
+            output_file_path: String::from(path.to_str().unwrap()),
+            ..Params::default()
+        };
+        let mut rs = RecordingState::initial_state(&p, &Cadence::every(1)).unwrap();
+        record(&SimState::initial_state(&p), &p, &mut rs);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "timestep\n");
+
+        let nowhere = Params {
+            output_file_path: String::from(temp_dir().join("no_such_folder").join("out.csv").to_str().unwrap()),
+            ..p
+        };
+        assert!(RecordingState::initial_state(&nowhere, &Cadence::every(1)).is_err());
     }
 }
@@ -0,0 +1,154 @@
+/* What the GUI's replay mode plays back: the surfaces of a finished run, from a binary trajectory (file_io::trajectory),
+   a snapshots.csv, or a folder of snapshot_<timestep>.csv files (file_io::snapshots), plus the recorder values the run
+   wrote to its output CSV, looked up by timestep. */
+use std::error::Error;
+use std::fs;
+
+use csv::Reader;
+
+use file_io::snapshots::read_snapshots;
+use file_io::trajectory::{Trajectory, MAGIC};
+use graph::types::ThickSurface;
+
+pub enum ReplaySource {
+    Trajectory(Trajectory),
+    // In timestep order
+    Snapshots(Vec<(u64, ThickSurface)>),
+}
+
+impl ReplaySource {
+    // Trajectories are told apart from snapshot CSVs by their magic bytes, folders are read as PerStep snapshots
+    pub fn open(path: &str) -> Result<ReplaySource, Box<dyn Error>> {
+        if fs::metadata(path)?.is_dir() {
+            let mut files: Vec<String> = fs::read_dir(path)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|n| n.starts_with("snapshot_") && n.ends_with(".csv"))
+                .collect();
+            files.sort();
+            let mut frames = Vec::new();
+            for f in files {
+                frames.append(&mut read_snapshots(&format!("{}/{}", path, f))?);
+            }
+            frames.sort_by_key(|(t, _)| *t);
+            return ReplaySource::from_snapshots(frames).map_err(|e| From::from(format!("{}: {}", path, e)));
+        }
+        if fs::read(path)?.starts_with(MAGIC) {
+            return Ok(ReplaySource::Trajectory(Trajectory::open(path)?));
+        }
+        ReplaySource::from_snapshots(read_snapshots(path)?).map_err(|e| From::from(format!("{}: {}", path, e)))
+    }
+
+    pub fn from_snapshots(frames: Vec<(u64, ThickSurface)>) -> Result<ReplaySource, String> {
+        if frames.is_empty() {
+            return Err(String::from("no snapshots to replay"));
+        }
+        Ok(ReplaySource::Snapshots(frames))
+    }
+
+    pub fn timesteps(&self) -> Vec<u64> {
+        match self {
+            ReplaySource::Trajectory(t) => t.timesteps(),
+            ReplaySource::Snapshots(frames) => frames.iter().map(|(t, _)| *t).collect(),
+        }
+    }
+
+    // Like Trajectory::surface_at: the last surface at or before timestep
+    pub fn surface_at(&self, timestep: u64) -> Result<ThickSurface, String> {
+        match self {
+            ReplaySource::Trajectory(t) => t.surface_at(timestep),
+            ReplaySource::Snapshots(frames) => match frames.iter().rev().find(|(t, _)| *t <= timestep) {
+                Some((_, ts)) => Ok(ts.clone()),
+                None => Err(format!("the snapshots start after timestep {}", timestep)),
+            },
+        }
+    }
+}
+
+// The CSV written by file_io::recorders: a timestep column, then the recorders' columns
+pub struct RecorderLog {
+    pub names: Vec<String>,
+    // In timestep order, NaN where a recorder wasn't due (an empty cell)
+    pub rows: Vec<(u64, Vec<f64>)>,
+}
+
+impl RecorderLog {
+    pub fn read(file_path: &str) -> Result<RecorderLog, Box<dyn Error>> {
+        let mut reader = Reader::from_path(file_path)?;
+        let header = reader.headers()?.clone();
+        if header.get(0) != Some("timestep") {
+            return Err(From::from(format!("{}: the first column should be timestep", file_path)));
+        }
+        let names = header.iter().skip(1).map(String::from).collect();
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let values = record
+                .iter()
+                .skip(1)
+                .map(|v| if v.is_empty() { Ok(f64::NAN) } else { v.parse::<f64>() })
+                .collect::<Result<Vec<f64>, _>>()?;
+            rows.push((record[0].parse::<u64>()?, values));
+        }
+        rows.sort_by_key(|(t, _)| *t);
+        Ok(RecorderLog { names, rows })
+    }
+
+    // Every column's last value at or before timestep (recorders have their own cadences, so not all from one row).
+    // None before the first row
+    pub fn values_at(&self, timestep: u64) -> Option<Vec<f64>> {
+        let upto = self.rows.iter().take_while(|(t, _)| *t <= timestep).count();
+        if upto == 0 {
+            return None;
+        }
+        let latest = |c: usize| self.rows[..upto].iter().rev().map(|(_, v)| v[c]).find(|v| !v.is_nan()).unwrap_or(f64::NAN);
+        Some((0..self.names.len()).map(latest).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_io::snapshots::{SnapshotLayout, SnapshotWriter};
+    use file_io::trajectory::{TrajectoryParams, TrajectoryWriter};
+    use graph::circular_thick_surface;
+    use graph::types::OUTER;
+    use std::env::temp_dir;
+
+    #[test]
+    fn every_source_replays_the_same_run() {
+        let folder = temp_dir().join("sann_replay_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let folder = folder.to_str().unwrap();
+
+        let per_step = SnapshotWriter::create(folder, SnapshotLayout::PerStep).unwrap();
+        let appended = SnapshotWriter::create(folder, SnapshotLayout::Appended).unwrap();
+        let trajectory_path = format!("{}/trajectory.bin", folder);
+        let mut trajectory = TrajectoryWriter::create(&trajectory_path, &TrajectoryParams::default()).unwrap();
+        let mut surfaces = Vec::new();
+        for (i, t) in vec![0, 10, 20].into_iter().enumerate() {
+            let ts = circular_thick_surface(1.0 + i as f64, 0.3, 20);
+            per_step.write(&ts, t).unwrap();
+            appended.write(&ts, t).unwrap();
+            trajectory.record(&ts, t).unwrap();
+            surfaces.push(ts);
+        }
+        trajectory.flush().unwrap();
+
+        for path in [String::from(folder), appended.path_for(0), trajectory_path] {
+            let source = ReplaySource::open(&path).unwrap();
+            assert_eq!(source.timesteps(), vec![0, 10, 20]);
+            let at_15 = source.surface_at(15).unwrap();
+            assert_eq!(at_15.layers[OUTER].to_vec_of_points(), surfaces[1].layers[OUTER].to_vec_of_points());
+        }
+
+        let log_path = format!("{}/output.csv", folder);
+        fs::write(&log_path, "timestep,energy,outer perimeter\n0,1.5,6.2\n10,1.25,6.4\n12,1.0,\n").unwrap();
+        let log = RecorderLog::read(&log_path).unwrap();
+        assert_eq!(log.names, vec!["energy", "outer perimeter"]);
+        assert_eq!(log.values_at(15), Some(vec![1.0, 6.4]));
+        assert_eq!(log.values_at(9), Some(vec![1.5, 6.2]));
+        assert!(RecorderLog { names: vec![], rows: vec![(5, vec![])] }.values_at(4).is_none());
+    }
+}
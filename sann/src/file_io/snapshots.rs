@@ -0,0 +1,187 @@
+/* Snapshots of the surface during a run, keyed by timestep. Every row is one node, and the nodes of each layer come
+   in next order, so the rows of a layer read as its polygon even after adds and merges have shuffled Graph::nodes.
+   The convex hull of the outer layer is written along with the two layers, as layer "hull". */
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use csv::Reader;
+
+use graph::convex_hull::convex_hull_from_graph;
+use graph::types::{Graph, NodeHandle, ThickSurface, INNER, OUTER};
+use graph::{cyclic_graph_from_coords, graph_to_points, traversal};
+use linalg_helpers::signed_area;
+
+pub const HEADER: &str = "timestep,layer,node_id,handle,x,y";
+const HULL: &str = "hull";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotLayout {
+    // snapshot_<timestep>.csv for every snapshot
+    PerStep,
+    // Every snapshot appended to snapshots.csv
+    Appended,
+}
+
+fn layer_name(layer: usize) -> &'static str {
+    match layer {
+        OUTER => "outer",
+        INNER => "inner",
+        _ => "layer",
+    }
+}
+
+fn write_layer(file: &mut File, timestep: u64, name: &str, g: &Graph) -> Result<(), Box<dyn Error>> {
+    for n in traversal(g) {
+        writeln!(file, "{},{},{},{},{},{}", timestep, name, n.id, n.handle.0, n.x, n.y)?;
+    }
+    Ok(())
+}
+
+fn write_rows(file: &mut File, ts: &ThickSurface, timestep: u64) -> Result<(), Box<dyn Error>> {
+    for (i, g) in ts.layers.iter().enumerate() {
+        write_layer(file, timestep, layer_name(i), g)?;
+    }
+    write_layer(file, timestep, HULL, &convex_hull_from_graph(&ts.layers[OUTER]))
+}
+
+pub struct SnapshotWriter {
+    folder: String,
+    layout: SnapshotLayout,
+}
+
+impl SnapshotWriter {
+    // With SnapshotLayout::Appended this starts a fresh snapshots.csv, so snapshots of an earlier run don't get mixed in
+    pub fn create(folder: &str, layout: SnapshotLayout) -> Result<SnapshotWriter, Box<dyn Error>> {
+        let w = SnapshotWriter {
+            folder: String::from(folder),
+            layout,
+        };
+        if layout == SnapshotLayout::Appended {
+            writeln!(File::create(w.path_for(0))?, "{}", HEADER)?;
+        }
+        Ok(w)
+    }
+
+    pub fn path_for(&self, timestep: u64) -> String {
+        match self.layout {
+            SnapshotLayout::PerStep => format!("{}/snapshot_{:09}.csv", self.folder, timestep),
+            SnapshotLayout::Appended => format!("{}/snapshots.csv", self.folder),
+        }
+    }
+
+    // Returns the file the snapshot went to
+    pub fn write(&self, ts: &ThickSurface, timestep: u64) -> Result<String, Box<dyn Error>> {
+        let path = self.path_for(timestep);
+        let mut file = match self.layout {
+            SnapshotLayout::PerStep => {
+                let mut f = File::create(&path)?;
+                writeln!(f, "{}", HEADER)?;
+                f
+            }
+            SnapshotLayout::Appended => OpenOptions::new().append(true).open(&path)?,
+        };
+        write_rows(&mut file, ts, timestep)?;
+        Ok(path)
+    }
+}
+
+// Plain x,y CSV with the header the dados_*.csv files have always had
+pub fn write_points_csv(points: &Vec<(f64, f64)>, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(file_path)?;
+    writeln!(file, "Componente1,Componente2")?;
+    for (x, y) in points {
+        writeln!(file, "{},{}", x, y)?;
+    }
+    Ok(())
+}
+
+// dados_out.csv, dados_in.csv and dados_ext.csv (the hull): the latest surface, for the modes that read a finished run.
+// Returns the files written
+pub fn write_latest(ts: &ThickSurface, folder: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let files = vec![format!("{}/dados_out.csv", folder), format!("{}/dados_in.csv", folder), format!("{}/dados_ext.csv", folder)];
+    write_points_csv(&graph_to_points(&ts.layers[OUTER]), &files[0])?;
+    write_points_csv(&graph_to_points(&ts.layers[INNER]), &files[1])?;
+    write_points_csv(&graph_to_points(&convex_hull_from_graph(&ts.layers[OUTER])), &files[2])?;
+    Ok(files)
+}
+
+// A node of a snapshot: its handle and position
+type Row = (NodeHandle, f64, f64);
+
+fn layer_from_rows(rows: &[Row]) -> Graph {
+    let coords = rows.iter().map(|(_, x, y)| (*x, *y)).collect();
+    let mut g = cyclic_graph_from_coords(&coords);
+    // A clockwise layer comes back with everything after rows[0] reversed
+    let reversed = signed_area(&coords) < 0.0;
+    for n in &mut g.nodes {
+        let row = if reversed && n.id > 0 { rows.len() - n.id } else { n.id };
+        n.handle = rows[row].0;
+    }
+    g.handles_issued = rows.iter().map(|(h, _, _)| h.0 + 1).max().unwrap_or(0);
+    g
+}
+
+// Every snapshot in a file written by SnapshotWriter, in timestep order, without the hulls
+pub fn read_snapshots(file_path: &str) -> Result<Vec<(u64, ThickSurface)>, Box<dyn Error>> {
+    let mut frames: BTreeMap<u64, (Vec<Row>, Vec<Row>)> = BTreeMap::new();
+    for record in Reader::from_path(file_path)?.records() {
+        let record = record?;
+        if record.len() != 6 {
+            return Err(From::from(format!("{}: expected the columns {}, found {} columns", file_path, HEADER, record.len())));
+        }
+        let frame = frames.entry(record[0].parse::<u64>()?).or_insert((vec![], vec![]));
+        let row = (NodeHandle(record[3].parse::<u64>()?), record[4].parse::<f64>()?, record[5].parse::<f64>()?);
+        match &record[1] {
+            "outer" => frame.0.push(row),
+            "inner" => frame.1.push(row),
+            HULL => {}
+            other => return Err(From::from(format!("{}: unknown layer \"{}\"", file_path, other))),
+        }
+    }
+    let mut ret = Vec::new();
+    for (timestep, (outer, inner)) in frames {
+        if outer.len() < 3 || inner.len() < 3 {
+            return Err(From::from(format!("{}: timestep {} needs at least 3 nodes per layer", file_path, timestep)));
+        }
+        ret.push((timestep, ThickSurface::new(layer_from_rows(&outer), layer_from_rows(&inner))));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::refine::split_long_edges;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn snapshots_come_out_in_traversal_order() {
+        let folder = temp_dir().join("sann_snapshots_test");
+        fs::create_dir_all(&folder).unwrap();
+        let folder = folder.to_str().unwrap();
+
+        let mut ts = circular_thick_surface(1.0, 0.3, 20);
+        let w = SnapshotWriter::create(folder, SnapshotLayout::Appended).unwrap();
+        w.write(&ts, 0).unwrap();
+        // New nodes go at the end of Graph::nodes but in the middle of the polygon
+        split_long_edges(&mut ts.layers[OUTER], 0.2);
+        w.write(&ts, 1000).unwrap();
+
+        let frames = read_snapshots(&w.path_for(1000)).unwrap();
+        assert_eq!(frames.iter().map(|(t, _)| *t).collect::<Vec<_>>(), vec![0, 1000]);
+        let (before, after) = (&frames[0].1, &frames[1].1);
+        assert_eq!(before.layers[OUTER].nodes.len(), 20);
+        assert_eq!(after.layers[OUTER].nodes.len(), ts.layers[OUTER].nodes.len());
+        for (read, written) in traversal(&after.layers[OUTER]).iter().zip(traversal(&ts.layers[OUTER])) {
+            assert_eq!((read.handle, read.x, read.y), (written.handle, written.x, written.y));
+        }
+
+        let per_step = SnapshotWriter::create(folder, SnapshotLayout::PerStep).unwrap();
+        assert!(per_step.write(&ts, 7).unwrap().ends_with("snapshot_000000007.csv"));
+        assert_eq!(read_snapshots(&per_step.path_for(7)).unwrap()[0].0, 7);
+    }
+}
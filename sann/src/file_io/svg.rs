@@ -0,0 +1,279 @@
+/* SVG figures of a ThickSurface, so figures come straight out of a run instead of going through the CSVs and
+   plot_coord.py. Everything is drawn in surface coordinates times scale (y pointing up, like the plots), with margin
+   pixels of empty space around the bounding box of the layers. From the back: the convex hull, gray and white matter,
+   the stitching, and the nodes of any colour fields. */
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use analysis::thickness;
+use graph::convex_hull::convex_hull_from_graph;
+use graph::refine::curvature;
+use graph::traversal;
+use graph::types::{Graph, ThickSurface, INNER, OUTER};
+use stitcher::types::Stitching;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerStyle {
+    // Any SVG colour: "#808080", "black", "none"...
+    pub stroke: String,
+    pub stroke_width: f64,
+    pub fill: String,
+    // Dash and gap lengths in pixels, solid if empty
+    pub dashes: Vec<f64>,
+}
+
+impl LayerStyle {
+    pub fn line(stroke: &str, stroke_width: f64) -> LayerStyle {
+        LayerStyle {
+            stroke: String::from(stroke),
+            stroke_width,
+            fill: String::from("none"),
+            dashes: vec![],
+        }
+    }
+
+    pub fn filled(stroke: &str, stroke_width: f64, fill: &str) -> LayerStyle {
+        LayerStyle {
+            fill: String::from(fill),
+            ..LayerStyle::line(stroke, stroke_width)
+        }
+    }
+
+    fn attributes(&self) -> String {
+        let mut ret = format!(
+            "fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"",
+            self.fill, self.stroke, self.stroke_width
+        );
+        if !self.dashes.is_empty() {
+            let dashes: Vec<String> = self.dashes.iter().map(|d| d.to_string()).collect();
+            ret.push_str(&format!(" stroke-dasharray=\"{}\"", dashes.join(",")));
+        }
+        ret
+    }
+}
+
+// What stitching = true in [svg] draws the correspondences with, unless stitching_colour says otherwise
+pub const STITCHING_COLOUR: &str = "#c04040";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgParams {
+    // Pixels per unit of surface coordinates
+    pub scale: f64,
+    // Empty pixels on each side of the layers
+    pub margin: f64,
+    pub outer: LayerStyle,
+    pub inner: LayerStyle,
+    // None leaves them out
+    pub hull: Option<LayerStyle>,
+    pub stitching: Option<LayerStyle>,
+    // Radius of the dots colour fields are drawn with
+    pub node_radius: f64,
+    // Fields (see named_field) that write_svg_of draws on top
+    pub colour_by: Vec<String>,
+}
+
+impl Default for SvgParams {
+    // Gray and white matter on a 400 pixel wide initial circle (radius 1), the hull dashed, no stitching
+    fn default() -> SvgParams {
+        SvgParams {
+            scale: 200.0,
+            margin: 20.0,
+            outer: LayerStyle::filled("black", 1.0, "#b0b0b0"),
+            inner: LayerStyle::filled("black", 1.0, "white"),
+            hull: Some(LayerStyle {
+                dashes: vec![4.0, 4.0],
+                ..LayerStyle::line("#4060c0", 1.0)
+            }),
+            stitching: None,
+            node_radius: 2.0,
+            colour_by: vec![],
+        }
+    }
+}
+
+// One value per node of a layer (indexed by node id), drawn as dots going from low_colour at min to high_colour at max
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeField {
+    pub layer: usize,
+    pub values: Vec<f64>,
+    pub low_colour: (u8, u8, u8),
+    pub high_colour: (u8, u8, u8),
+}
+
+impl NodeField {
+    // Blue for the smallest values, red for the largest
+    pub fn new(layer: usize, values: Vec<f64>) -> NodeField {
+        NodeField {
+            layer,
+            values,
+            low_colour: (40, 80, 220),
+            high_colour: (220, 40, 40),
+        }
+    }
+
+    fn colour_of(&self, v: f64, min: f64, max: f64) -> String {
+        let t = if max > min { ((v - min) / (max - min)).clamp(0.0, 1.0) } else { 0.5 };
+        let mix = |a: u8, b: u8| (a as f64 + t * (b as f64 - a as f64)).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            mix(self.low_colour.0, self.high_colour.0),
+            mix(self.low_colour.1, self.high_colour.1),
+            mix(self.low_colour.2, self.high_colour.2)
+        )
+    }
+}
+
+// Fields that can be asked for by name in SvgParams::colour_by
+pub const FIELD_NAMES: [&str; 3] = ["thickness", "outer_curvature", "inner_curvature"];
+
+pub fn named_field(ts: &ThickSurface, name: &str) -> Result<NodeField, String> {
+    let curvatures = |g: &Graph| g.nodes.iter().map(|n| curvature(g, n)).collect();
+    match name {
+        "thickness" => Ok(NodeField::new(OUTER, thickness::from_nearest_points(ts))),
+        "outer_curvature" => Ok(NodeField::new(OUTER, curvatures(&ts.layers[OUTER]))),
+        "inner_curvature" => Ok(NodeField::new(INNER, curvatures(&ts.layers[INNER]))),
+        _ => Err(format!("unknown colour field \"{}\", expected one of {:?}", name, FIELD_NAMES)),
+    }
+}
+
+// Surface coordinates to SVG ones
+struct Frame {
+    scale: f64,
+    margin: f64,
+    min_x: f64,
+    max_y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Frame {
+    fn fit(ts: &ThickSurface, p: &SvgParams) -> Frame {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for n in ts.layers.iter().flat_map(|g| g.nodes.iter()) {
+            min_x = min_x.min(n.x);
+            min_y = min_y.min(n.y);
+            max_x = max_x.max(n.x);
+            max_y = max_y.max(n.y);
+        }
+        Frame {
+            scale: p.scale,
+            margin: p.margin,
+            min_x,
+            max_y,
+            width: (max_x - min_x) * p.scale + 2.0 * p.margin,
+            height: (max_y - min_y) * p.scale + 2.0 * p.margin,
+        }
+    }
+
+    // y is flipped: SVG's y points down
+    fn to_svg(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.min_x) * self.scale + self.margin, (self.max_y - y) * self.scale + self.margin)
+    }
+}
+
+fn polygon(g: &Graph, frame: &Frame, style: &LayerStyle) -> String {
+    let points: Vec<String> = traversal(g)
+        .iter()
+        .map(|n| {
+            let (x, y) = frame.to_svg(n.x, n.y);
+            format!("{:.3},{:.3}", x, y)
+        })
+        .collect();
+    format!("  <polygon points=\"{}\" {}/>\n", points.join(" "), style.attributes())
+}
+
+pub fn to_svg(ts: &ThickSurface, stitching: Option<&Stitching>, fields: &Vec<NodeField>, p: &SvgParams) -> String {
+    let frame = Frame::fit(ts, p);
+    let mut ret = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.3}\" height=\"{h:.3}\" viewBox=\"0 0 {w:.3} {h:.3}\">\n",
+        w = frame.width,
+        h = frame.height
+    );
+    if let Some(style) = &p.hull {
+        ret.push_str(&polygon(&convex_hull_from_graph(&ts.layers[OUTER]), &frame, style));
+    }
+    ret.push_str(&polygon(&ts.layers[OUTER], &frame, &p.outer));
+    ret.push_str(&polygon(&ts.layers[INNER], &frame, &p.inner));
+    if let (Some(s), Some(style)) = (stitching, &p.stitching) {
+        ret.push_str(&format!("  <g {}>\n", style.attributes()));
+        for (o, i) in s.positions(ts) {
+            let (o, i) = (&ts.layers[OUTER].nodes[o], &ts.layers[INNER].nodes[i]);
+            let ((x1, y1), (x2, y2)) = (frame.to_svg(o.x, o.y), frame.to_svg(i.x, i.y));
+            ret.push_str(&format!("    <line x1=\"{:.3}\" y1=\"{:.3}\" x2=\"{:.3}\" y2=\"{:.3}\"/>\n", x1, y1, x2, y2));
+        }
+        ret.push_str("  </g>\n");
+    }
+    for f in fields {
+        let min = f.values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = f.values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        ret.push_str("  <g stroke=\"none\">\n");
+        for (n, v) in ts.layers[f.layer].nodes.iter().zip(f.values.iter()) {
+            let (x, y) = frame.to_svg(n.x, n.y);
+            ret.push_str(&format!(
+                "    <circle cx=\"{:.3}\" cy=\"{:.3}\" r=\"{}\" fill=\"{}\"/>\n",
+                x,
+                y,
+                p.node_radius,
+                f.colour_of(*v, min, max)
+            ));
+        }
+        ret.push_str("  </g>\n");
+    }
+    ret.push_str("</svg>\n");
+    ret
+}
+
+pub fn write_svg(ts: &ThickSurface, stitching: Option<&Stitching>, fields: &Vec<NodeField>, p: &SvgParams, file_path: &str) -> Result<(), Box<dyn Error>> {
+    File::create(file_path)?.write_all(to_svg(ts, stitching, fields, p).as_bytes())?;
+    Ok(())
+}
+
+// write_svg with the fields named in p.colour_by
+pub fn write_svg_of(ts: &ThickSurface, stitching: Option<&Stitching>, p: &SvgParams, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let fields = p.colour_by.iter().map(|name| named_field(ts, name)).collect::<Result<Vec<NodeField>, String>>()?;
+    write_svg(ts, stitching, &fields, p, file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use stitcher::stitch_default;
+
+    #[test]
+    fn scale_and_margin_set_the_size() {
+        let ts = circular_thick_surface(1.0, 0.3, 40);
+        let p = SvgParams {
+            scale: 100.0,
+            margin: 10.0,
+            ..SvgParams::default()
+        };
+        let svg = to_svg(&ts, None, &vec![], &p);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"220.000\" height=\"220.000\""));
+        // Hull, outer and inner
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        // Node 0 of the outer layer is at (1, 0): the right edge of the layers, halfway down
+        assert!(svg.contains("<polygon points=\"210.000,110.000 "));
+        assert!(!svg.contains("<line"));
+    }
+
+    #[test]
+    fn stitching_and_fields_are_drawn() {
+        let ts = circular_thick_surface(1.0, 0.3, 40);
+        let s = stitch_default(&ts);
+        let p = SvgParams {
+            hull: None,
+            stitching: Some(LayerStyle::line("red", 0.5)),
+            ..SvgParams::default()
+        };
+        let field = NodeField::new(OUTER, (0..40).map(|i| i as f64).collect());
+        let svg = to_svg(&ts, Some(&s), &vec![field.clone()], &p);
+        assert_eq!(svg.matches("<polygon").count(), 2);
+        assert_eq!(svg.matches("<line").count(), s.positions(&ts).len());
+        assert_eq!(svg.matches("<circle").count(), 40);
+        assert!(svg.contains("fill=\"#2850dc\"") && svg.contains("fill=\"#dc2828\""));
+        assert_eq!(field.colour_of(5.0, 5.0, 5.0), field.colour_of(0.5, 0.0, 1.0));
+        assert!(named_field(&ts, "thickness").is_ok() && named_field(&ts, "color").is_err());
+    }
+}
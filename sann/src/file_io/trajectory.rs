@@ -0,0 +1,359 @@
+/* Binary trajectories: a whole run in one appendable file, from which the surface at any recorded timestep can be
+   rebuilt. The first record is a keyframe (every node of every layer) and so is one every keyframe_every steps; in
+   between, a step only stores the nodes that differ from the step before. Accepted moves change a few positions,
+   additions and merges change a few nodes' links (a merge also moves the last node into the freed slot and shortens
+   the layer), so a patch is the new layer length plus those nodes. Remeshes change everything and get a keyframe.
+
+   Little endian throughout. The file starts with MAGIC, then records:
+     kind: u8 (KEYFRAME or PATCH), timestep: u64, layers: u32, then for every layer
+       handles_issued: u64, num_nodes: u32, [PATCH only: num_changed: u32], num_nodes (or num_changed) nodes
+   and a node is id: u32, handle: u64, x: f64, y: f64, next_id: u32, prev_id: u32, pinned: u8. */
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+
+use graph::types::{Graph, Node, NodeHandle, ThickSurface};
+
+pub const MAGIC: &[u8; 8] = b"SANNTRJ1";
+const KEYFRAME: u8 = 0;
+const PATCH: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrajectoryParams {
+    // Record every this many steps
+    pub every: u64,
+    // Steps between keyframes. Rebuilding a surface replays at most this many patches
+    pub keyframe_every: u64,
+}
+
+impl Default for TrajectoryParams {
+    fn default() -> TrajectoryParams {
+        TrajectoryParams {
+            every: 1,
+            keyframe_every: 1000,
+        }
+    }
+}
+
+fn put_node(buf: &mut Vec<u8>, n: &Node) {
+    buf.extend_from_slice(&(n.id as u32).to_le_bytes());
+    buf.extend_from_slice(&n.handle.0.to_le_bytes());
+    buf.extend_from_slice(&n.x.to_le_bytes());
+    buf.extend_from_slice(&n.y.to_le_bytes());
+    buf.extend_from_slice(&(n.next_id as u32).to_le_bytes());
+    buf.extend_from_slice(&(n.prev_id as u32).to_le_bytes());
+    buf.push(n.pinned as u8);
+}
+
+// Nodes of now that aren't the same in before (or weren't there)
+fn changed_nodes<'a>(before: &Graph, now: &'a Graph) -> Vec<&'a Node> {
+    now.nodes.iter().filter(|n| before.nodes.get(n.id) != Some(*n)).collect()
+}
+
+pub struct TrajectoryWriter {
+    file: BufWriter<File>,
+    params: TrajectoryParams,
+    last: Option<ThickSurface>,
+    last_keyframe: u64,
+}
+
+impl TrajectoryWriter {
+    // Starts a new trajectory at file_path, replacing whatever was there
+    pub fn create(file_path: &str, params: &TrajectoryParams) -> Result<TrajectoryWriter, Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(file_path)?);
+        file.write_all(MAGIC)?;
+        Ok(TrajectoryWriter {
+            file,
+            params: params.clone(),
+            last: None,
+            last_keyframe: 0,
+        })
+    }
+
+    // Carries on a trajectory written earlier (by a run that got interrupted, say). The first record is a keyframe
+    pub fn append(file_path: &str, params: &TrajectoryParams) -> Result<TrajectoryWriter, Box<dyn Error>> {
+        Trajectory::open(file_path)?;
+        Ok(TrajectoryWriter {
+            file: BufWriter::new(OpenOptions::new().append(true).open(file_path)?),
+            params: params.clone(),
+            last: None,
+            last_keyframe: 0,
+        })
+    }
+
+    pub fn wants(&self, timestep: u64) -> bool {
+        timestep.is_multiple_of(self.params.every)
+    }
+
+    // Steps where nothing changed aren't written: the surface there is the one from the record before
+    pub fn record(&mut self, ts: &ThickSurface, timestep: u64) -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::new();
+        let keyframe_due = self.last.is_none() || timestep >= self.last_keyframe + self.params.keyframe_every;
+        let patches: Option<Vec<Vec<&Node>>> = match &self.last {
+            Some(last) if !keyframe_due && last.layers.len() == ts.layers.len() => {
+                Some(last.layers.iter().zip(ts.layers.iter()).map(|(b, n)| changed_nodes(b, n)).collect())
+            }
+            _ => None,
+        };
+        let total_nodes: usize = ts.layers.iter().map(|g| g.nodes.len()).sum();
+        match patches {
+            // A patch with most of the nodes in it (a remesh) is no smaller than a keyframe
+            Some(ref p) if p.iter().map(|c| c.len()).sum::<usize>() * 2 < total_nodes => {
+                let unchanged = self.last.as_ref().is_some_and(|last| {
+                    p.iter().all(|c| c.is_empty())
+                        && last.layers.iter().zip(ts.layers.iter()).all(|(b, n)| b.nodes.len() == n.nodes.len() && b.handles_issued == n.handles_issued)
+                });
+                if unchanged {
+                    return Ok(());
+                }
+                buf.push(PATCH);
+                buf.extend_from_slice(&timestep.to_le_bytes());
+                buf.extend_from_slice(&(ts.layers.len() as u32).to_le_bytes());
+                for (g, changed) in ts.layers.iter().zip(p.iter()) {
+                    buf.extend_from_slice(&g.handles_issued.to_le_bytes());
+                    buf.extend_from_slice(&(g.nodes.len() as u32).to_le_bytes());
+                    buf.extend_from_slice(&(changed.len() as u32).to_le_bytes());
+                    for n in changed {
+                        put_node(&mut buf, n);
+                    }
+                }
+            }
+            _ => {
+                buf.push(KEYFRAME);
+                buf.extend_from_slice(&timestep.to_le_bytes());
+                buf.extend_from_slice(&(ts.layers.len() as u32).to_le_bytes());
+                for g in &ts.layers {
+                    buf.extend_from_slice(&g.handles_issued.to_le_bytes());
+                    buf.extend_from_slice(&(g.nodes.len() as u32).to_le_bytes());
+                    for n in &g.nodes {
+                        put_node(&mut buf, n);
+                    }
+                }
+                self.last_keyframe = timestep;
+            }
+        }
+        self.file.write_all(&buf)?;
+        self.last = Some(ts.clone());
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.at + n > self.bytes.len() {
+            return Err(format!("trajectory cut short at byte {}", self.at));
+        }
+        self.at += n;
+        Ok(&self.bytes[self.at - n..self.at])
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    fn node(&mut self) -> Result<Node, String> {
+        Ok(Node {
+            id: self.u32()? as usize,
+            handle: NodeHandle(self.u64()?),
+            x: self.f64()?,
+            y: self.f64()?,
+            next_id: self.u32()? as usize,
+            prev_id: self.u32()? as usize,
+            pinned: self.u8()? != 0,
+        })
+    }
+}
+
+// Where each record starts, and what it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Record {
+    timestep: u64,
+    keyframe: bool,
+    offset: usize,
+}
+
+pub struct Trajectory {
+    bytes: Vec<u8>,
+    records: Vec<Record>,
+}
+
+impl Trajectory {
+    pub fn open(file_path: &str) -> Result<Trajectory, Box<dyn Error>> {
+        Trajectory::from_bytes(std::fs::read(file_path)?).map_err(|e| From::from(format!("{}: {}", file_path, e)))
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Trajectory, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(String::from("not a trajectory file"));
+        }
+        let mut records = Vec::new();
+        let mut c = Cursor { bytes: &bytes, at: MAGIC.len() };
+        while c.at < bytes.len() {
+            let offset = c.at;
+            let keyframe = match c.u8()? {
+                KEYFRAME => true,
+                PATCH => false,
+                other => return Err(format!("unknown record kind {} at byte {}", other, offset)),
+            };
+            let timestep = c.u64()?;
+            if records.is_empty() && !keyframe {
+                return Err(String::from("the first record has to be a keyframe"));
+            }
+            for _ in 0..c.u32()? {
+                c.u64()?;
+                let num_nodes = c.u32()?;
+                let stored = if keyframe { num_nodes } else { c.u32()? };
+                // Every node is 37 bytes
+                c.take(stored as usize * 37)?;
+            }
+            records.push(Record { timestep, keyframe, offset });
+        }
+        Ok(Trajectory { bytes, records })
+    }
+
+    // Every timestep with a record, in order
+    pub fn timesteps(&self) -> Vec<u64> {
+        self.records.iter().map(|r| r.timestep).collect()
+    }
+
+    fn apply(&self, r: &Record, layers: &mut Vec<Graph>) -> Result<(), String> {
+        let mut c = Cursor {
+            bytes: &self.bytes,
+            at: r.offset + 9,
+        };
+        let num_layers = c.u32()? as usize;
+        if r.keyframe {
+            layers.clear();
+        } else if num_layers != layers.len() {
+            return Err(format!("timestep {} patches {} layers, the surface has {}", r.timestep, num_layers, layers.len()));
+        }
+        for i in 0..num_layers {
+            let handles_issued = c.u64()?;
+            let num_nodes = c.u32()? as usize;
+            if r.keyframe {
+                let nodes = (0..num_nodes).map(|_| c.node()).collect::<Result<Vec<Node>, String>>()?;
+                layers.push(Graph { nodes, handles_issued });
+            } else {
+                let g = &mut layers[i];
+                g.handles_issued = handles_issued;
+                let num_changed = c.u32()?;
+                let changed = (0..num_changed).map(|_| c.node()).collect::<Result<Vec<Node>, String>>()?;
+                // Shorter layers lose their last nodes, longer ones get placeholders that the patch then fills in
+                g.nodes.truncate(num_nodes);
+                while g.nodes.len() < num_nodes {
+                    let placeholder = g.nodes[0].clone();
+                    g.nodes.push(placeholder);
+                }
+                for n in changed {
+                    if n.id >= num_nodes {
+                        return Err(format!("timestep {} has node {} in a layer of {}", r.timestep, n.id, num_nodes));
+                    }
+                    let id = n.id;
+                    g.nodes[id] = n;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The surface as it was at timestep: the last record at or before it, rebuilt from the keyframe before that
+    pub fn surface_at(&self, timestep: u64) -> Result<ThickSurface, String> {
+        let last = match self.records.iter().rposition(|r| r.timestep <= timestep) {
+            Some(i) => i,
+            None => return Err(format!("the trajectory starts after timestep {}", timestep)),
+        };
+        let keyframe = self.records[..=last].iter().rposition(|r| r.keyframe).unwrap();
+        let mut layers = Vec::new();
+        for r in &self.records[keyframe..=last] {
+            self.apply(r, &mut layers)?;
+        }
+        Ok(ThickSurface { layers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::effects::{apply_changes, random_change};
+    use graph::refine::{merge_short_runs, split_long_edges};
+    use graph::resample::even_out;
+    use graph::types::{NodeChangeMap, INNER, OUTER};
+    use std::env::temp_dir;
+
+    #[test]
+    fn surfaces_are_rebuilt_at_any_timestep() {
+        let path = temp_dir().join("sann_trajectory_test.bin");
+        let path = path.to_str().unwrap();
+        let params = TrajectoryParams {
+            every: 1,
+            keyframe_every: 25,
+        };
+        let mut w = TrajectoryWriter::create(path, &params).unwrap();
+        let mut rng = rand::thread_rng();
+        let mut ts = circular_thick_surface(1.0, 0.3, 60);
+        let mut history = vec![];
+        for t in 0..60 {
+            match t {
+                // Nothing happens on 10, and on 20 there's an addition, on 30 a merge and on 40 a remesh
+                10 => {}
+                20 => assert!(split_long_edges(&mut ts.layers[OUTER], 0.1) > 0),
+                30 => assert!(merge_short_runs(&mut ts, INNER, 0.2, 3, false) > 0),
+                40 => ts = even_out(&ts),
+                _ => {
+                    let change = random_change(&ts.layers[OUTER], (-0.01, 0.01), &mut rng);
+                    let mut changes = NodeChangeMap::new();
+                    changes.insert(change.id, change);
+                    apply_changes(&mut ts.layers[OUTER], &changes);
+                }
+            }
+            w.record(&ts, t).unwrap();
+            history.push(ts.clone());
+        }
+        w.flush().unwrap();
+
+        let trajectory = Trajectory::open(path).unwrap();
+        assert!(!trajectory.timesteps().contains(&10));
+        assert_eq!(trajectory.timesteps().last(), Some(&59));
+        for t in vec![0, 10, 19, 20, 24, 25, 31, 40, 59, 1000] {
+            let rebuilt = trajectory.surface_at(t).unwrap();
+            let expected = &history[(t as usize).min(59)];
+            for (g, h) in rebuilt.layers.iter().zip(expected.layers.iter()) {
+                assert_eq!(g.nodes, h.nodes);
+                assert_eq!(g.handles_issued, h.handles_issued);
+            }
+        }
+
+        let mut more = TrajectoryWriter::append(path, &params).unwrap();
+        more.record(&ts, 60).unwrap();
+        more.flush().unwrap();
+        assert_eq!(Trajectory::open(path).unwrap().timesteps().last(), Some(&60));
+        assert!(Trajectory::from_bytes(b"SANNTRJ1\x01".to_vec()).is_err());
+    }
+}
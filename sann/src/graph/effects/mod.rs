@@ -15,7 +15,10 @@ fn apply_change(g: &mut Graph, change: &NodeChange) {
         g.nodes[change.id].x = change.cur_x + change.delta_x;
         g.nodes[change.id].y = change.cur_y + change.delta_y;
     } else {
-        panic!("CARILHO")
+        panic!(
+            "Applying a change to node {}: expected it at ({}, {}), found it at ({}, {})",
+            change.id, change.cur_x, change.cur_y, g.nodes[change.id].x, g.nodes[change.id].y
+        )
     }
 }
 
@@ -25,7 +28,14 @@ fn revert_change(g: &mut Graph, change: &NodeChange) {
         g.nodes[change.id].x = change.cur_x;
         g.nodes[change.id].y = change.cur_y;
     } else {
-        panic!("CARILHO")
+        panic!(
+            "Reverting a change to node {}: expected it at ({}, {}), found it at ({}, {})",
+            change.id,
+            change.cur_x + change.delta_x,
+            change.cur_y + change.delta_y,
+            g.nodes[change.id].x,
+            g.nodes[change.id].y
+        )
     }
 }
 
@@ -43,15 +53,22 @@ pub(crate) fn revert_changes(g: &mut Graph, changes: &NodeChangeMap) {
     }
 }
 
-fn random_node(g: &Graph, rng: &mut rand::rngs::ThreadRng) -> NodeIndex {
+// Any node that isn't pinned. None if they all are
+fn random_node<R: Rng>(g: &Graph, rng: &mut R) -> Option<NodeIndex> {
+    let free: Vec<NodeIndex> = g.nodes.iter().filter(|n| !n.pinned).map(|n| n.id).collect();
+    if free.is_empty() {
+        return None;
+    }
     let annoyingly_needed_due_to_rusts_type_inference: usize = rng.gen();
-    annoyingly_needed_due_to_rusts_type_inference % g.nodes.len()
+    Some(free[annoyingly_needed_due_to_rusts_type_inference % free.len()])
 }
 
-pub fn random_change(g: &Graph, (low, high): (f64, f64), rng: &mut rand::rngs::ThreadRng) -> NodeChange {
-    let to_change = random_node(g, rng);
-    let x_change = rng.gen_range(low, high);
-    let y_change = rng.gen_range(low, high);
+// With every node pinned, a change that doesn't move anything
+pub fn random_change<R: Rng>(g: &Graph, (low, high): (f64, f64), rng: &mut R) -> NodeChange {
+    let (to_change, x_change, y_change) = match random_node(g, rng) {
+        Some(id) => (id, rng.gen_range(low, high), rng.gen_range(low, high)),
+        None => (0, 0.0, 0.0),
+    };
     NodeChange {
         id: to_change,
         cur_x: g.nodes[to_change].x,
@@ -96,10 +113,11 @@ pub fn smooth_change_out(g: &Graph, change: NodeChange, how_smooth: Smooth<usize
         let enough_next = dist_traveled_next.as_f64() > how_smooth_f64;
         let enough_prev = dist_traveled_prev.as_f64() > how_smooth_f64;
 
-        if !enough_next {
+        // Pinned nodes are passed over, the ones after them still get their share
+        if !enough_next && !cur_next.pinned {
             ret.insert(cur_next.id, mk_change(&cur_next, change, how_smooth_f64, dist_traveled_next.as_f64()));
         }
-        if !enough_prev {
+        if !enough_prev && !cur_prev.pinned {
             ret.insert(cur_prev.id, mk_change(&cur_prev, change, how_smooth_f64, dist_traveled_prev.as_f64()));
         }
         if enough_next && enough_prev {
@@ -109,10 +127,14 @@ pub fn smooth_change_out(g: &Graph, change: NodeChange, how_smooth: Smooth<usize
     ret
 }
 
+// The new node always gets a fresh handle, whatever the addition was planned with
 pub fn add_node_(ts: &mut ThickSurface, layer_to_which_add: usize, node_addition: &NodeAddition) {
-    ts.layers[layer_to_which_add].nodes[node_addition.n.next_id].prev_id = node_addition.n.id;
-    ts.layers[layer_to_which_add].nodes[node_addition.n.prev_id].next_id = node_addition.n.id;
-    ts.layers[layer_to_which_add].nodes.insert(node_addition.n.id, node_addition.n.clone());
+    let g = &mut ts.layers[layer_to_which_add];
+    let mut n = node_addition.n.clone();
+    n.handle = g.new_handle();
+    g.nodes[n.next_id].prev_id = n.id;
+    g.nodes[n.prev_id].next_id = n.id;
+    g.nodes.insert(n.id, n);
 }
 
 // Returns prev id
@@ -122,7 +144,7 @@ fn kill(id: usize, g: &mut Graph) -> usize {
     g.nodes[prev_id].next_id = next_id;
     g.nodes[next_id].prev_id = prev_id;
 
-    /* 2. Swap deleted with last, if id isnt last, because that would be pointless */
+    /* 2. Swap deleted with last, if id isnt last, because that would be pointless. The moved node keeps its handle */
     let last = g.nodes.last().unwrap().clone();
     if id != last.id {
         g.nodes[last.prev_id].next_id = id;
@@ -177,14 +199,44 @@ pub fn changer_of_choice(
     _compression_factor: f64,
     s: &Stitching,
 ) -> NodeChangeMap {
-    pusher_1::push_inners(inner_graph, outer_graph, other_graph_changes, s)
+    let pushed = pusher_1::push_inners(inner_graph, outer_graph, other_graph_changes, s);
+    // Whatever the pusher does, pinned nodes stay where they are
+    let mut ret = NodeChangeMap::new();
+    for (id, change) in pushed {
+        if !inner_graph.nodes[id].pinned {
+            ret.insert(id, change);
+        }
+    }
+    ret
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use graph::{area, circular_graph, circular_thick_surface, cyclic_graph_from_coords, node_to_add};
-    use linalg_helpers::circular_points;
+    use graph::{area, available_node_id, circular_graph, circular_thick_surface, cyclic_graph_from_coords};
+    use linalg_helpers::{circular_points, lines_intersection};
+
+    // The midpoint of an edge longer than addition_threshold (refinement itself splits edges with graph::refine)
+    fn node_to_add(g: &Graph, prev: &Node, next: &Node, addition_threshold: f64) -> Option<NodeAddition> {
+        if prev.next(g).id == next.id && next.prev(g).id == prev.id && /* Might be worth moving all conditions to a function */
+            distance_between_nodes(prev, next) > addition_threshold
+        {
+            let new_node_id = available_node_id(g);
+
+            let new_node = Node {
+                id: new_node_id,
+                handle: g.next_handle(),
+                x: (prev.x + next.x) / 2.0,
+                y: (prev.y + next.y) / 2.0,
+                next_id: next.id,
+                prev_id: prev.id,
+                pinned: prev.pinned && next.pinned,
+            };
+            Some(NodeAddition { n: new_node })
+        } else {
+            None
+        }
+    }
 
     fn assert_cyclicness(g: &Graph) {
         let fst = &g.nodes[0];
@@ -267,6 +319,40 @@ mod tests {
         assert!(len == len_after + merging.dist);
     }
 
+    #[test]
+    fn handles_follow_nodes_around() {
+        let g = circular_graph(0.0, 0.0, 1.0, 20);
+        let before = g.clone();
+        let mut my_ts = ThickSurface {
+            layers: vec![g, circular_graph(0.0, 0.0, 0.3, 18)],
+        };
+        let merging = NodeMerging {
+            one_end: before.nodes[19].clone(),
+            oth_end: before.nodes[0].clone(),
+            dist: 8,
+            layer_id: OUTER,
+            survivor_x: before.nodes[19].x,
+            survivor_y: before.nodes[19].y,
+        };
+        merge_nodes_(&mut my_ts, &merging);
+        let after = &my_ts.layers[OUTER];
+        let positions = after.positions();
+        let mut gone = 0;
+        for n in &before.nodes {
+            match positions.get(&n.handle) {
+                Some(i) => assert_eq!((after.nodes[*i].x, after.nodes[*i].y), (n.x, n.y)),
+                None => gone += 1,
+            }
+        }
+        assert_eq!(gone, 8);
+
+        let to_add = node_to_add(after, &after.nodes[0], after.nodes[0].next(after), 0.0).unwrap();
+        add_node_(&mut my_ts, OUTER, &to_add);
+        let added = my_ts.layers[OUTER].nodes.last().unwrap();
+        assert_eq!(added.handle, NodeHandle(20));
+        assert_eq!(my_ts.layers[OUTER].positions().get(&NodeHandle(20)), Some(&added.id));
+    }
+
     #[test]
     fn random_node_is_changed() {
         // TODO: This should be generated
@@ -700,16 +786,14 @@ mod tests {
             (-0.6056444294878965, -0.5857180225242566, -0.6052886641292006, -0.5667445584325539),
         ];
 
+        // lines2 was taken to be lines1 with its crossing undone, but both still have it: in each, lines[182] and
+        // lines[183] cross near (-0.61, -0.58), and nothing before them does
         let hmmmm = lines_intersection(&lines1);
         let hmmmm2 = lines_intersection(&lines2);
 
-        assert!(match hmmmm {
-            Some(_) => true,
-            _ => true,
-        });
-        assert!(match hmmmm2 {
-            Some(_) => false,
-            _ => true,
-        });
+        assert!(hmmmm.is_some());
+        assert!(hmmmm2.is_some());
+        assert!(lines_intersection(&lines1[..182]).is_none());
+        assert!(lines_intersection(&lines2[..182]).is_none());
     }
 }
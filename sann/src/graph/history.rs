@@ -0,0 +1,103 @@
+/* Undo/redo for hand edits (the draw modes). Keeps whole snapshots instead of inverse operations: the shapes being
+   edited are small, and a snapshot can't get out of sync with whatever best_effort_add/best_effort_delete did. */
+
+#[derive(Clone, Debug)]
+pub struct History<T> {
+    done: Vec<T>,
+    undone: Vec<T>,
+    limit: usize,
+}
+
+impl<T: Clone> History<T> {
+    // Keeps at most limit snapshots to undo to, dropping the oldest ones
+    pub fn new(limit: usize) -> History<T> {
+        History {
+            done: Vec::new(),
+            undone: Vec::new(),
+            limit,
+        }
+    }
+
+    // Call with the state from right before an edit. A new edit throws away whatever could have been redone
+    pub fn record(&mut self, before: T) {
+        self.done.push(before);
+        if self.done.len() > self.limit {
+            self.done.remove(0);
+        }
+        self.undone.clear();
+    }
+
+    // The state before the last edit, or current itself if there's nothing to undo
+    pub fn undo(&mut self, current: T) -> T {
+        match self.done.pop() {
+            Some(before) => {
+                self.undone.push(current);
+                before
+            }
+            None => current,
+        }
+    }
+
+    pub fn redo(&mut self, current: T) -> T {
+        match self.undone.pop() {
+            Some(after) => {
+                self.done.push(current);
+                after
+            }
+            None => current,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::types::OUTER;
+
+    #[test]
+    fn undo_and_redo_surface_edits() {
+        let mut ts = circular_thick_surface(1.0, 0.3, 20);
+        let mut h = History::new(10);
+
+        h.record(ts.clone());
+        ts.best_effort_add(1.05, 0.05).unwrap();
+        h.record(ts.clone());
+        ts.best_effort_delete(0.0, 1.0).unwrap();
+        assert_eq!(ts.layers[OUTER].nodes.len(), 20);
+
+        ts = h.undo(ts);
+        assert_eq!(ts.layers[OUTER].nodes.len(), 21);
+        ts = h.undo(ts);
+        assert_eq!(ts.layers[OUTER].nodes.len(), 20);
+        assert!(!h.can_undo());
+        ts = h.undo(ts);
+        assert_eq!(ts.layers[OUTER].nodes.len(), 20);
+
+        ts = h.redo(ts);
+        assert_eq!(ts.layers[OUTER].nodes.len(), 21);
+        h.record(ts.clone());
+        assert!(!h.can_redo());
+    }
+
+    #[test]
+    fn only_the_last_few_edits_are_kept() {
+        let mut h = History::new(2);
+        let mut v = 0;
+        for _ in 0..5 {
+            h.record(v);
+            v += 1;
+        }
+        v = h.undo(v);
+        v = h.undo(v);
+        assert_eq!(h.undo(v), 3);
+    }
+}
@@ -1,29 +1,52 @@
 pub mod convex_hull;
 pub mod effects;
+pub mod history;
+pub mod pins;
+pub mod polygon;
+pub mod refine;
+pub mod resample;
+pub mod separation;
+pub mod spatial;
 pub mod types;
+pub mod validate;
 
 use graph::effects::merge_nodes_;
 use graph::types::*;
 use linalg_helpers;
-use linalg_helpers::{dist, lines_intersection};
+use graph::spatial::first_crossing;
+use linalg_helpers::dist;
 
+/* Every graph is stored counter-clockwise, so areas come out positive. Clockwise coordinates (drawn, read from a file...)
+   are taken in reverse, keeping the first point as node 0 */
 pub fn cyclic_graph_from_coords(node_coordinates: &Vec<(f64, f64)>) -> Graph {
-    let mut to_return: Graph = Graph { nodes: Vec::new() };
+    let mut counter_clockwise = node_coordinates.clone();
+    if linalg_helpers::signed_area(node_coordinates) < 0.0 {
+        counter_clockwise[1..].reverse();
+    }
+    let node_coordinates = &counter_clockwise;
     let num_points = node_coordinates.len();
+    let mut to_return: Graph = Graph {
+        nodes: Vec::new(),
+        handles_issued: num_points as u64,
+    };
     to_return.nodes.push(Node {
         id: 0,
+        handle: NodeHandle(0),
         x: node_coordinates[0].0,
         y: node_coordinates[0].1,
         next_id: 1,
         prev_id: num_points - 1,
+        pinned: false,
     });
     for i in 1..num_points {
         let new_node = Node {
             id: i,
+            handle: NodeHandle(i as u64),
             x: node_coordinates[i].0,
             y: node_coordinates[i].1,
             next_id: (i + 1) % num_points,
             prev_id: i - 1,
+            pinned: false,
         };
         to_return.nodes.push(new_node);
     }
@@ -48,6 +71,7 @@ pub fn gray_matter_area(ts: &ThickSurface) -> f64 {
     area(&ts.layers[OUTER]) - area(&ts.layers[INNER])
 }
 
+// Signed: positive for counter-clockwise graphs, which is what every graph in here should be
 pub fn area(g: &Graph) -> f64 {
     let mut ret = 0.0;
     for n in &g.nodes {
@@ -60,6 +84,31 @@ pub fn area(g: &Graph) -> f64 {
     ret / 2.0
 }
 
+pub fn is_counter_clockwise(g: &Graph) -> bool {
+    area(g) > 0.0
+}
+
+// Flips a clockwise graph around in place by swapping every node's next and prev
+pub fn make_counter_clockwise(g: &mut Graph) {
+    if !is_counter_clockwise(g) {
+        for n in &mut g.nodes {
+            std::mem::swap(&mut n.next_id, &mut n.prev_id);
+        }
+    }
+}
+
+// Even-odd rule: is (x, y) inside the polygon g?
+pub fn contains_point(g: &Graph, x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for n in &g.nodes {
+        let next = n.next(g);
+        if (n.y > y) != (next.y > y) && x < n.x + (y - n.y) * (next.x - n.x) / (next.y - n.y) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
 pub fn perimeter(g: &Graph) -> f64 {
     let mut ret = 0.0;
     let first = &g.nodes[0];
@@ -77,21 +126,20 @@ pub fn perimeter(g: &Graph) -> f64 {
     ret
 }
 
-fn graph_to_lines(g: &Graph) -> Vec<(f64, f64, f64, f64)> {
-    let mut ret = Vec::new();
-    for n in &g.nodes {
-        let n_next = n.next(g);
-        ret.push((n.x, n.y, n_next.x, n_next.y));
+// Nodes in next order starting from nodes[0], which after adds and merges isn't the order they're stored in
+pub fn traversal(g: &Graph) -> Vec<&Node> {
+    let first = &g.nodes[0];
+    let mut ret = vec![first];
+    let mut cur = first.next(g);
+    while cur != first && ret.len() < g.nodes.len() {
+        ret.push(cur);
+        cur = cur.next(g);
     }
     ret
 }
 
 pub fn graph_to_points(g: &Graph) -> Vec<(f64, f64)> {
-    let mut ret = Vec::new();
-    for n in &g.nodes {        
-        ret.push((n.x, n.y));
-    }
-    ret
+    traversal(g).iter().map(|n| (n.x, n.y)).collect()
 }
 
 pub fn closest_node_to_some_point(graph: &Graph, some_point_x: f64, some_point_y: f64) -> &Node {
@@ -143,15 +191,6 @@ pub fn closest_nodes_across_all_layers(ts: &ThickSurface, some_point_x: f64, som
     ret
 }
 
-pub fn graphs_to_lines(graphs: &Vec<Graph>) -> Vec<(f64, f64, f64, f64)> {
-    let mut ret = Vec::new();
-    for g in graphs {
-        let mut lines = graph_to_lines(g);
-        ret.append(&mut lines);
-    }
-    ret
-}
-
 pub fn distance_between_points(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
     linalg_helpers::norm(x1 - x2, y1 - y2)
 }
@@ -164,32 +203,10 @@ pub fn available_node_id(g: &Graph) -> usize {
     g.nodes.len()
 }
 
-pub fn node_to_add(g: &Graph, prev: &Node, next: &Node, addition_threshold: f64) -> Option<NodeAddition> {
-    if prev.next(g).id == next.id && next.prev(g).id == prev.id && /* Might be worth moving all conditions to a function */
-        distance_between_nodes(prev, next) > addition_threshold
-    {
-        let new_node_id = available_node_id(g);
-
-        let new_node = Node {
-            id: new_node_id,
-            x: (prev.x + next.x) / 2.0,
-            y: (prev.y + next.y) / 2.0,
-            next_id: next.id,
-            prev_id: prev.id,
-        };
-        Some(NodeAddition { n: new_node })
-    } else {
-        None
-    }
-}
-
 fn merging_wouldnt_add_intersection(ts: &ThickSurface, node_merging: &NodeMerging) -> bool {
     let mut simulated_ts = ts.clone();
     merge_nodes_(&mut simulated_ts, node_merging);
-    match lines_intersection(&graphs_to_lines(&simulated_ts.layers)) {
-        Some(_) => false,
-        None => true,
-    }
+    first_crossing(&simulated_ts.layers).is_none()
 }
 
 fn can_merge(ts: &ThickSurface, node_merging: &NodeMerging, deletion_threshold: f64) -> bool {
@@ -218,9 +235,16 @@ pub fn nodes_to_merge(
     max_merge_steps_away: usize,
     check_ints: bool,
 ) -> Option<NodeMerging> {
+    // Pinned nodes can't be moved, so they can't be merged into others or be what others merge into
+    if src.pinned {
+        return None;
+    }
     for i in 1..max_merge_steps_away + 1 {
         let nnnn = src.clone();
         let mmmm = src.next_by(&ts.layers[layer_id], i).clone();
+        if mmmm.pinned {
+            return None;
+        }
         let (avg_x, avg_y) = ((nnnn.x + mmmm.x) / 2.0, (nnnn.y + mmmm.y) / 2.0);
         let m = NodeMerging {
             one_end: nnnn,
@@ -276,4 +300,22 @@ mod tests {
         assert!(perimeter(&test_circ) < 6.30);
         assert!(perimeter(&test_circ) > 6.26);
     }
+
+    #[test]
+    fn clockwise_graphs_are_turned_around() {
+        let clockwise = vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+        let g = cyclic_graph_from_coords(&clockwise);
+        assert!(area(&g) > 0.99);
+        assert_eq!((g.nodes[0].x, g.nodes[0].y), (0.0, 0.0));
+        assert_eq!((g.nodes[0].next(&g).x, g.nodes[0].next(&g).y), (1.0, 0.0));
+
+        let mut by_hand = g.clone();
+        for n in &mut by_hand.nodes {
+            std::mem::swap(&mut n.next_id, &mut n.prev_id);
+        }
+        assert!(area(&by_hand) < 0.0);
+        let ts = ThickSurface::new(by_hand, circular_graph(0.5, 0.5, 0.2, 10));
+        assert!(is_counter_clockwise(&ts.layers[OUTER]));
+        assert!((gray_matter_area(&ts) - (1.0 - area(&ts.layers[INNER]))).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,120 @@
+/* Pinned arcs of nodes, like the base of the cerebellum where it's attached to the brainstem. Pinned nodes are never
+   picked or pushed by the moves, never merged, and edges between two of them split into pinned nodes, so a pinned arc
+   stays where it was for the whole run. The mark is Node::pinned, which travels with the node as ids change. */
+use graph::types::{Graph, ThickSurface, INNER, OUTER};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PinRange {
+    // Degrees, counter-clockwise from the positive x axis, measured around the origin. from > to wraps through 0
+    Angles(f64, f64),
+    // Node ids of the surface as it's set up, both ends included. first > last wraps through 0
+    Indices(usize, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinnedRegion {
+    pub layers: Vec<usize>,
+    pub range: PinRange,
+}
+
+impl PinnedRegion {
+    pub fn both_layers(range: PinRange) -> PinnedRegion {
+        PinnedRegion {
+            layers: vec![OUTER, INNER],
+            range,
+        }
+    }
+}
+
+fn within<T: PartialOrd>(v: T, from: T, to: T) -> bool {
+    if from <= to {
+        from <= v && v <= to
+    } else {
+        v >= from || v <= to
+    }
+}
+
+fn pin_layer(g: &mut Graph, range: &PinRange) {
+    for n in &mut g.nodes {
+        let inside = match range {
+            PinRange::Angles(from, to) => within(n.y.atan2(n.x).to_degrees().rem_euclid(360.0), *from, *to),
+            PinRange::Indices(first, last) => within(n.id, *first, *last),
+        };
+        n.pinned = n.pinned || inside;
+    }
+}
+
+pub fn pin(ts: &mut ThickSurface, regions: &Vec<PinnedRegion>) {
+    for r in regions {
+        for layer in &r.layers {
+            pin_layer(&mut ts.layers[*layer], &r.range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::effects::{changer_of_choice, random_change, smooth_change_out};
+    use graph::refine::{merge_short_runs, split_long_edges};
+    use graph::types::Smooth;
+    use stitcher::stitch_default;
+
+    fn num_pinned(g: &Graph) -> usize {
+        g.nodes.iter().filter(|n| n.pinned).count()
+    }
+
+    #[test]
+    fn regions_pick_the_right_nodes() {
+        let mut ts = circular_thick_surface(1.0, 0.3, 36);
+        pin(
+            &mut ts,
+            &vec![
+                PinnedRegion::both_layers(PinRange::Angles(345.0, 15.0)),
+                PinnedRegion {
+                    layers: vec![INNER],
+                    range: PinRange::Indices(18, 20),
+                },
+            ],
+        );
+        // Every 10 degrees: 350, 0 and 10
+        assert_eq!(num_pinned(&ts.layers[OUTER]), 3);
+        assert_eq!(num_pinned(&ts.layers[INNER]), 6);
+        assert!(ts.layers[INNER].nodes[19].pinned && !ts.layers[OUTER].nodes[19].pinned);
+    }
+
+    #[test]
+    fn pinned_nodes_stay_put() {
+        let mut ts = circular_thick_surface(1.0, 0.3, 40);
+        pin(&mut ts, &vec![PinnedRegion::both_layers(PinRange::Indices(30, 9))]);
+        let s = stitch_default(&ts);
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let change = random_change(&ts.layers[OUTER], (-0.1, 0.1), &mut rng);
+            assert!(!ts.layers[OUTER].nodes[change.id].pinned);
+            let smoothed = smooth_change_out(&ts.layers[OUTER], change, Smooth::Count(15));
+            let pushed = changer_of_choice(&ts.layers[INNER], &ts.layers[OUTER], &smoothed, 1.1, &s);
+            assert!(smoothed.unwrap().keys().all(|id| !ts.layers[OUTER].nodes[*id].pinned));
+            assert!(pushed.unwrap().keys().all(|id| !ts.layers[INNER].nodes[*id].pinned));
+        }
+    }
+
+    #[test]
+    fn pins_follow_their_nodes() {
+        let mut ts = circular_thick_surface(1.0, 0.3, 40);
+        pin(&mut ts, &vec![PinnedRegion::both_layers(PinRange::Indices(0, 9))]);
+        let before: Vec<(f64, f64)> = ts.layers[OUTER].nodes.iter().filter(|n| n.pinned).map(|n| (n.x, n.y)).collect();
+
+        // Every edge gets one new node. The 9 between two pinned nodes are pinned, the others free
+        assert_eq!(split_long_edges(&mut ts.layers[OUTER], 0.1), 40);
+        assert_eq!(num_pinned(&ts.layers[OUTER]), 19);
+
+        // Nothing pinned gets merged away, or moved by a merge
+        assert!(merge_short_runs(&mut ts, OUTER, 0.2, 3, false) > 0);
+        assert_eq!(num_pinned(&ts.layers[OUTER]), 19);
+        for p in before {
+            assert!(ts.layers[OUTER].nodes.iter().any(|n| n.pinned && (n.x, n.y) == p));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+/* Conversions between our layers and geo's types, so the geo algorithms (simplify, Hausdorff distance, buffer...) and
+   GIS formats (file_io::gis) work on surfaces directly. A ThickSurface is a geo::Polygon with the outer layer as its
+   exterior and the inner layer as its only hole: the polygon itself is the gray matter. */
+use geo::{Coord, LineString, Polygon};
+
+use graph::types::{Graph, ThickSurface, INNER, OUTER};
+use graph::validate::containment;
+use graph::{cyclic_graph_from_coords, traversal};
+
+// Closed (the first point repeated at the end, like geo wants), in next order
+pub fn graph_to_line_string(g: &Graph) -> LineString<f64> {
+    let mut coords: Vec<Coord<f64>> = traversal(g).iter().map(|n| Coord { x: n.x, y: n.y }).collect();
+    coords.push(coords[0]);
+    LineString::new(coords)
+}
+
+// A fresh graph with ids in ring order. The closing point is dropped whether or not the ring repeats it
+pub fn line_string_to_graph(l: &LineString<f64>) -> Graph {
+    let mut points: Vec<(f64, f64)> = l.coords().map(|c| (c.x, c.y)).collect();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    cyclic_graph_from_coords(&points)
+}
+
+pub fn thick_surface_to_polygon(ts: &ThickSurface) -> Polygon<f64> {
+    Polygon::new(graph_to_line_string(&ts.layers[OUTER]), vec![graph_to_line_string(&ts.layers[INNER])])
+}
+
+// Only polygons with exactly one hole that make a proper surface (see validate::containment) are surfaces
+pub fn polygon_to_thick_surface(p: &Polygon<f64>) -> Result<ThickSurface, String> {
+    if p.interiors().len() != 1 {
+        return Err(format!("a surface is a polygon with exactly one hole, this one has {}", p.interiors().len()));
+    }
+    let rings = [p.exterior(), &p.interiors()[0]];
+    if rings.iter().any(|r| r.coords().count() < 4) {
+        return Err(String::from("each layer needs at least 3 points"));
+    }
+    let ts = ThickSurface::new(line_string_to_graph(rings[OUTER]), line_string_to_graph(rings[INNER]));
+    match containment(&ts) {
+        Some(v) => Err(format!("not a proper surface: {}", v)),
+        None => Ok(ts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::algorithm::area::Area;
+    use graph::{circular_thick_surface, gray_matter_area};
+
+    #[test]
+    fn surfaces_go_through_geo_and_back() {
+        let ts = circular_thick_surface(1.0, 0.3, 50);
+        let p = thick_surface_to_polygon(&ts);
+        assert_eq!(p.exterior().coords().count(), 51);
+        assert!((p.unsigned_area() - gray_matter_area(&ts)).abs() < 1e-9);
+
+        let back = polygon_to_thick_surface(&p).unwrap();
+        for (g, h) in ts.layers.iter().zip(back.layers.iter()) {
+            assert_eq!(g.to_vec_of_points(), h.to_vec_of_points());
+        }
+
+        // Swapping the layers puts the outer one in the hole
+        let swapped = Polygon::new(p.interiors()[0].clone(), vec![p.exterior().clone()]);
+        assert!(polygon_to_thick_surface(&swapped).is_err());
+        assert!(polygon_to_thick_surface(&Polygon::new(p.exterior().clone(), vec![])).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+/* Batch node additions and merges, a whole layer per pass.
+   Everything that has to change is decided on the layer as it was at the start of the pass and then done at once,
+   so no index gets invalidated halfway through (which is why the one-at-a-time versions had to stop after one node). */
+use std::collections::HashSet;
+
+use graph::spatial::PointGrid;
+use graph::types::{Graph, Node, ThickSurface};
+use graph::{distance_between_nodes, nodes_to_merge, NodeMerging};
+use graph::spatial::first_crossing;
+use types::Params;
+
+// Merging never takes a layer below a triangle
+const MIN_NODES: usize = 3;
+
+/* Splits every edge longer than addition_threshold into equal pieces no longer than it.
+   New nodes go at the end of the vector, so the ids of the existing ones don't change. Returns how many were added */
+pub fn split_long_edges(g: &mut Graph, addition_threshold: f64) -> usize {
+    let thresholds = vec![addition_threshold; g.nodes.len()];
+    split_edges(g, &thresholds, usize::MAX)
+}
+
+/* Same, with each edge (indexed by the id of the node it starts at) having its own threshold, and adding at most
+   max_new_nodes. When they don't all fit, the edges furthest over their threshold get split first */
+pub fn split_edges(g: &mut Graph, thresholds: &[f64], max_new_nodes: usize) -> usize {
+    let mut long_edges: Vec<(usize, usize, f64)> = g
+        .nodes
+        .iter()
+        .map(|n| (n.id, n.next_id, distance_between_nodes(n, n.next(g)) / thresholds[n.id]))
+        .filter(|(_, _, ratio)| *ratio > 1.0)
+        .collect();
+    long_edges.sort_by(|(_, _, a), (_, _, b)| b.partial_cmp(a).unwrap());
+
+    let mut added = 0;
+    for (prev_id, next_id, ratio) in long_edges {
+        let pieces = (ratio.ceil() as usize).min((max_new_nodes - added).saturating_add(1));
+        if pieces < 2 {
+            break;
+        }
+        let (a, b) = (g.nodes[prev_id].clone(), g.nodes[next_id].clone());
+        let mut last = prev_id;
+        for k in 1..pieces {
+            let t = k as f64 / pieces as f64;
+            let id = g.nodes.len();
+            let handle = g.new_handle();
+            g.nodes.push(Node {
+                id,
+                handle,
+                x: a.x + t * (b.x - a.x),
+                y: a.y + t * (b.y - a.y),
+                next_id,
+                prev_id: last,
+                pinned: a.pinned && b.pinned,
+            });
+            g.nodes[last].next_id = id;
+            last = id;
+        }
+        g.nodes[next_id].prev_id = last;
+        added += pieces - 1;
+    }
+    added
+}
+
+/* Every merge nodes_to_merge finds going around the layer once, without two merges touching the same node.
+   The survivor of one merge can't be killed by another, and killed nodes don't start merges of their own */
+fn plan_merges(ts: &ThickSurface, layer_id: usize, thresholds: &[f64], max_merge_steps_away: usize) -> Vec<NodeMerging> {
+    let g = &ts.layers[layer_id];
+    let mut taken = HashSet::new();
+    let mut remaining = g.nodes.len();
+    let mut ret = Vec::new();
+    let mut cur = &g.nodes[0];
+    for _ in 0..g.nodes.len() {
+        if !taken.contains(&cur.id) {
+            if let Some(m) = nodes_to_merge(ts, layer_id, cur, thresholds[cur.id], max_merge_steps_away, false) {
+                let killed: Vec<usize> = (1..m.dist + 1).map(|i| cur.next_by(g, i).id).collect();
+                if remaining >= MIN_NODES + m.dist && !killed.iter().any(|id| taken.contains(id)) {
+                    taken.insert(cur.id);
+                    taken.extend(killed);
+                    remaining -= m.dist;
+                    ret.push(m);
+                }
+            }
+        }
+        cur = cur.next(g);
+    }
+    ret
+}
+
+// The layer after all the merges, with the survivors moved and the dead nodes compacted away (ids == indices again)
+fn apply_merges(g: &Graph, merges: &Vec<NodeMerging>) -> Graph {
+    let mut nodes = g.nodes.clone();
+    let mut dead = vec![false; nodes.len()];
+    for m in merges {
+        let mut victim = g.nodes[m.one_end.id].next(g);
+        for _ in 0..m.dist {
+            dead[victim.id] = true;
+            victim = victim.next(g);
+        }
+        // victim is now the first node after the merge
+        nodes[m.one_end.id].x = m.survivor_x;
+        nodes[m.one_end.id].y = m.survivor_y;
+        nodes[m.one_end.id].next_id = victim.id;
+        nodes[victim.id].prev_id = m.one_end.id;
+    }
+
+    let mut new_ids = vec![0; nodes.len()];
+    let mut alive = 0;
+    for i in 0..nodes.len() {
+        if !dead[i] {
+            new_ids[i] = alive;
+            alive += 1;
+        }
+    }
+    Graph {
+        nodes: nodes
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !dead[*i])
+            .map(|(_, n)| Node {
+                id: new_ids[n.id],
+                next_id: new_ids[n.next_id],
+                prev_id: new_ids[n.prev_id],
+                ..n
+            })
+            .collect(),
+        handles_issued: g.handles_issued,
+    }
+}
+
+/* Merges every run of nodes closer than deletion_threshold (up to max_merge_steps_away apart). With check_intersections,
+   if doing all of them at once makes the layers cross, they're taken one by one, keeping the ones that don't.
+   Returns how many nodes were removed */
+pub fn merge_short_runs(ts: &mut ThickSurface, layer_id: usize, deletion_threshold: f64, max_merge_steps_away: usize, check_intersections: bool) -> usize {
+    let thresholds = vec![deletion_threshold; ts.layers[layer_id].nodes.len()];
+    merge_runs(ts, layer_id, &thresholds, max_merge_steps_away, check_intersections)
+}
+
+// Same, with a threshold per node (the one a merge starting at that node has to be under)
+pub fn merge_runs(ts: &mut ThickSurface, layer_id: usize, thresholds: &[f64], max_merge_steps_away: usize, check_intersections: bool) -> usize {
+    let merges = plan_merges(ts, layer_id, thresholds, max_merge_steps_away);
+    if merges.is_empty() {
+        return 0;
+    }
+    let crosses = |ms: &Vec<NodeMerging>| {
+        let mut candidate = ts.clone();
+        candidate.layers[layer_id] = apply_merges(&ts.layers[layer_id], ms);
+        check_intersections && first_crossing(&candidate.layers).is_some()
+    };
+
+    let mut accepted = merges.clone();
+    if crosses(&merges) {
+        accepted = Vec::new();
+        for m in merges {
+            accepted.push(m);
+            if crosses(&accepted) {
+                accepted.pop();
+            }
+        }
+    }
+    ts.layers[layer_id] = apply_merges(&ts.layers[layer_id], &accepted);
+    accepted.iter().map(|m| m.dist).sum()
+}
+
+/* Makes the addition and deletion thresholds local. Both get multiplied by a scale that goes down where the layer is
+   curved and where the other layer is close, and up on flat stretches far from it, so nodes end up where the folding is.
+   With curvature k and distance d to the other layer, scale = (k * initial_radius)^-curvature_weight
+   * (d / initial_thickness)^proximity_weight, clamped to [min_scale, max_scale]: the initial annulus has scale 1 */
+#[derive(Clone, Debug)]
+pub struct AdaptiveRefinement {
+    pub curvature_weight: f64,
+    pub proximity_weight: f64,
+    pub min_scale: f64,
+    pub max_scale: f64,
+    // Nodes in both layers together. Additions stop there, merges don't
+    pub node_budget: usize,
+}
+
+impl Default for AdaptiveRefinement {
+    fn default() -> AdaptiveRefinement {
+        AdaptiveRefinement {
+            curvature_weight: 1.0,
+            proximity_weight: 1.0,
+            min_scale: 0.25,
+            max_scale: 4.0,
+            node_budget: 20_000,
+        }
+    }
+}
+
+// Menger curvature of the node with its two neighbors: 1 / radius of the circle through the three
+pub fn curvature(g: &Graph, n: &Node) -> f64 {
+    let (p, q) = (n.prev(g), n.next(g));
+    let twice_area = ((n.x - p.x) * (q.y - p.y) - (n.y - p.y) * (q.x - p.x)).abs();
+    let sides = distance_between_nodes(p, n) * distance_between_nodes(n, q) * distance_between_nodes(p, q);
+    if sides == 0.0 {
+        0.0
+    } else {
+        2.0 * twice_area / sides
+    }
+}
+
+// One scale per edge, indexed by the id of the node the edge starts at
+pub fn edge_scales(ts: &ThickSurface, layer_id: usize, a: &AdaptiveRefinement, p: &Params) -> Vec<f64> {
+    let g = &ts.layers[layer_id];
+    let others: Vec<PointGrid> = (0..ts.layers.len()).filter(|l| *l != layer_id).map(|l| PointGrid::from_graph(&ts.layers[l])).collect();
+    let node_curvatures: Vec<f64> = g.nodes.iter().map(|n| curvature(g, n)).collect();
+    g.nodes
+        .iter()
+        .map(|n| {
+            let next = n.next(g);
+            let k = node_curvatures[n.id].max(node_curvatures[next.id]);
+            let (mid_x, mid_y) = ((n.x + next.x) / 2.0, (n.y + next.y) / 2.0);
+            let d = others.iter().map(|o| o.nearest_distance(mid_x, mid_y)).fold(f64::INFINITY, f64::min);
+            let curvature_term = (k * p.initial_radius).max(1e-9).powf(-a.curvature_weight);
+            let proximity_term = (d / p.initial_thickness).max(1e-9).powf(a.proximity_weight);
+            (curvature_term * proximity_term).max(a.min_scale).min(a.max_scale)
+        })
+        .collect()
+}
+
+fn total_nodes(ts: &ThickSurface) -> usize {
+    ts.layers.iter().map(|g| g.nodes.len()).sum()
+}
+
+// Node additions as the parameters ask for them: plain node_addition_threshold, or adaptive within the node budget
+pub fn refine_additions(ts: &mut ThickSurface, layer_id: usize, p: &Params) -> usize {
+    match &p.adaptive_refinement {
+        None => split_long_edges(&mut ts.layers[layer_id], p.node_addition_threshold),
+        Some(a) => {
+            let thresholds: Vec<f64> = edge_scales(ts, layer_id, a, p).iter().map(|s| s * p.node_addition_threshold).collect();
+            let room = a.node_budget.saturating_sub(total_nodes(ts));
+            split_edges(&mut ts.layers[layer_id], &thresholds, room)
+        }
+    }
+}
+
+pub fn refine_merges(ts: &mut ThickSurface, layer_id: usize, p: &Params, check_intersections: bool) -> usize {
+    match &p.adaptive_refinement {
+        None => merge_short_runs(ts, layer_id, p.node_deletion_threshold, p.max_merge_steps_away, check_intersections),
+        Some(a) => {
+            let thresholds: Vec<f64> = edge_scales(ts, layer_id, a, p).iter().map(|s| s * p.node_deletion_threshold).collect();
+            merge_runs(ts, layer_id, &thresholds, p.max_merge_steps_away, check_intersections)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::types::OUTER;
+    use graph::validate::validate;
+    use graph::{circular_thick_surface, cyclic_graph_from_coords, perimeter};
+
+    #[test]
+    fn every_long_edge_is_split_in_one_pass() {
+        let mut square = cyclic_graph_from_coords(&vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert_eq!(split_long_edges(&mut square, 0.3), 12);
+        assert_eq!(square.nodes.len(), 16);
+        assert!((perimeter(&square) - 4.0).abs() < 1e-12);
+        for n in &square.nodes {
+            assert!(distance_between_nodes(n, n.next(&square)) <= 0.3);
+        }
+        let ts = ThickSurface { layers: vec![square] };
+        assert!(validate(&ts).is_empty());
+    }
+
+    #[test]
+    fn every_short_run_is_merged_in_one_pass() {
+        // Outer nodes are ~0.063 apart, so with one step away every other node goes
+        let mut ts = circular_thick_surface(1.0, 0.3, 100);
+        assert_eq!(merge_short_runs(&mut ts, OUTER, 0.07, 1, true), 50);
+        assert_eq!(ts.layers[OUTER].nodes.len(), 50);
+        assert!(validate(&ts).is_empty());
+
+        let mut tiny = circular_thick_surface(1.0, 0.3, 5);
+        merge_short_runs(&mut tiny, OUTER, 10.0, 3, false);
+        assert_eq!(tiny.layers[OUTER].nodes.len(), 3);
+    }
+
+    #[test]
+    fn adaptive_refinement_goes_where_the_layer_bends() {
+        let p = Params {
+            initial_radius: 1.0,
+            initial_thickness: 0.2,
+            ..Params::default()
+        };
+        let a = AdaptiveRefinement {
+            proximity_weight: 0.0,
+            ..AdaptiveRefinement::default()
+        };
+        // A long flat rectangle: the flat sides come out coarse, the corners fine
+        let rect = cyclic_graph_from_coords(&vec![(-2.0, -0.5), (-1.0, -0.5), (0.0, -0.5), (1.0, -0.5), (2.0, -0.5), (2.0, 0.5), (1.0, 0.5), (0.0, 0.5), (-1.0, 0.5), (-2.0, 0.5)]);
+        let ts = ThickSurface { layers: vec![rect] };
+        let scales = edge_scales(&ts, OUTER, &a, &p);
+        assert_eq!(scales[1], a.max_scale);
+        assert!(scales[4] < scales[1]);
+
+        let mut ts = circular_thick_surface(1.0, 0.2, 50);
+        let budgeted = Params {
+            node_addition_threshold: 0.01,
+            adaptive_refinement: Some(AdaptiveRefinement { node_budget: 120, ..a }),
+            ..p
+        };
+        refine_additions(&mut ts, OUTER, &budgeted);
+        assert_eq!(total_nodes(&ts), 120);
+        assert!(validate(&ts).is_empty());
+    }
+}
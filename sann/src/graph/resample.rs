@@ -0,0 +1,196 @@
+/* Redistributes the nodes of a layer evenly along its arc length.
+   Adding and merging nodes during a run leaves some stretches of a layer crowded and others sparse; resampling
+   gives back a layer with the same shape and uniform spacing, with ids that match positions again. Pinned nodes
+   (graph::pins) are kept as they are, and only the stretches between them are evened out. */
+use graph::{cyclic_graph_from_coords, traversal};
+use graph::types::{Graph, Node, NodeHandle, ThickSurface};
+use linalg_helpers::dist;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    NumPoints(usize),
+    // Target distance (along the layer) between consecutive nodes. The node count is rounded so the layer closes evenly
+    Spacing(f64),
+}
+
+// A closed layer needs at least a triangle
+const MIN_POINTS: usize = 3;
+
+fn num_points_for(r: Resolution, length: f64) -> usize {
+    match r {
+        Resolution::NumPoints(n) => n.max(MIN_POINTS),
+        Resolution::Spacing(s) => ((length / s).round() as usize).max(MIN_POINTS),
+    }
+}
+
+/* n points evenly spaced along the polyline points, starting at points[0]. A closed one loops back to points[0],
+   an open one stops one spacing short of its last point */
+fn evenly_spaced(points: &[(f64, f64)], closed: bool, n: usize) -> Vec<(f64, f64)> {
+    let num_segments = if closed { points.len() } else { points.len() - 1 };
+    // cumulative[i] is the arc length from points[0] to points[i]; with closed the last entry loops back to points[0]
+    let mut cumulative = vec![0.0];
+    for i in 0..num_segments {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        let so_far = cumulative[i];
+        cumulative.push(so_far + dist(a.0, a.1, b.0, b.1));
+    }
+    let length = cumulative[num_segments];
+
+    let mut segment = 0;
+    let mut ret = Vec::with_capacity(n);
+    for k in 0..n {
+        let target = length * k as f64 / n as f64;
+        while segment < num_segments - 1 && cumulative[segment + 1] <= target {
+            segment += 1;
+        }
+        let (a, b) = (points[segment], points[(segment + 1) % points.len()]);
+        let segment_length = cumulative[segment + 1] - cumulative[segment];
+        let t = if segment_length > 0.0 { (target - cumulative[segment]) / segment_length } else { 0.0 };
+        ret.push((a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1)));
+    }
+    ret
+}
+
+fn length_of(points: &[(f64, f64)]) -> f64 {
+    points.windows(2).map(|w| dist(w[0].0, w[0].1, w[1].0, w[1].1)).sum()
+}
+
+/* Pinned nodes stay exactly where they are (with their handles), and only the free stretches between them are
+   resampled, each getting a share of the free nodes proportional to its length */
+fn resample_around_pins(g: &Graph, nodes: Vec<&Node>, n: usize) -> Graph {
+    let points: Vec<(f64, f64)> = nodes.iter().map(|node| (node.x, node.y)).collect();
+    let pinned: Vec<bool> = nodes.iter().map(|node| node.pinned).collect();
+    let first_pinned = pinned.iter().position(|p| *p).unwrap();
+    let rotated: Vec<usize> = (0..points.len()).map(|i| (i + first_pinned) % points.len()).collect();
+    // Stretches from one pinned node to the next, both ends included
+    let mut stretches: Vec<Vec<usize>> = Vec::new();
+    for &i in &rotated {
+        if pinned[i] {
+            if let Some(s) = stretches.last_mut() {
+                s.push(i);
+            }
+            stretches.push(vec![i]);
+        } else {
+            stretches.last_mut().unwrap().push(i);
+        }
+    }
+    stretches.last_mut().unwrap().push(rotated[0]);
+
+    let num_pinned = pinned.iter().filter(|p| **p).count();
+    let free_budget = n.saturating_sub(num_pinned) as f64;
+    let lengths: Vec<f64> = stretches
+        .iter()
+        .map(|s| length_of(&s.iter().map(|i| points[*i]).collect::<Vec<_>>()))
+        .collect();
+    // Stretches of two are edges between neighboring pinned nodes, which get no new nodes
+    let free_length: f64 = stretches.iter().zip(lengths.iter()).filter(|(s, _)| s.len() > 2).map(|(_, l)| l).sum();
+
+    let mut coords = Vec::with_capacity(n);
+    let mut kept = Vec::with_capacity(n);
+    for (s, len) in stretches.iter().zip(lengths.iter()) {
+        let stretch_points: Vec<(f64, f64)> = s.iter().map(|i| points[*i]).collect();
+        let num_free = if s.len() == 2 || free_length == 0.0 {
+            0
+        } else {
+            (free_budget * len / free_length).round() as usize
+        };
+        let mut along = evenly_spaced(&stretch_points, false, num_free + 1).into_iter();
+        coords.push(along.next().unwrap());
+        kept.push(Some(nodes[s[0]].handle));
+        for c in along {
+            coords.push(c);
+            kept.push(None);
+        }
+    }
+
+    let mut ret = cyclic_graph_from_coords(&coords);
+    let mut issued = g.handles_issued;
+    for node in &mut ret.nodes {
+        match kept[node.id] {
+            Some(h) => {
+                node.handle = h;
+                node.pinned = true;
+            }
+            None => {
+                node.handle = NodeHandle(issued);
+                issued += 1;
+            }
+        }
+    }
+    ret.handles_issued = issued;
+    ret
+}
+
+pub fn resample(g: &Graph, r: Resolution) -> Graph {
+    let nodes = traversal(g);
+    let mut points: Vec<(f64, f64)> = nodes.iter().map(|node| (node.x, node.y)).collect();
+    points.push(points[0]);
+    let n = num_points_for(r, length_of(&points));
+    points.pop();
+    if nodes.iter().any(|node| node.pinned) {
+        return resample_around_pins(g, nodes, n);
+    }
+
+    // These are all new nodes, so their handles carry on from the old graph's instead of reusing them
+    let mut ret = cyclic_graph_from_coords(&evenly_spaced(&points, true, n));
+    for n in &mut ret.nodes {
+        n.handle = NodeHandle(g.handles_issued + n.handle.0);
+    }
+    ret.handles_issued += g.handles_issued;
+    ret
+}
+
+pub fn resample_thick_surface(ts: &ThickSurface, r: Resolution) -> ThickSurface {
+    ThickSurface {
+        layers: ts.layers.iter().map(|g| resample(g, r)).collect(),
+    }
+}
+
+// Every layer resampled to the number of nodes it already has: same resolution, uniform spacing
+pub fn even_out(ts: &ThickSurface) -> ThickSurface {
+    ThickSurface {
+        layers: ts.layers.iter().map(|g| resample(g, Resolution::NumPoints(g.nodes.len()))).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::{circular_graph, perimeter};
+
+    #[test]
+    fn square_is_split_evenly() {
+        let square = cyclic_graph_from_coords(&vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        let g = resample(&square, Resolution::NumPoints(8));
+        assert_eq!(g.nodes.len(), 8);
+        for n in &g.nodes {
+            assert!((dist(n.x, n.y, n.next(&g).x, n.next(&g).y) - 0.5).abs() < 1e-12);
+        }
+        assert!((g.nodes[3].x - 1.0).abs() < 1e-12 && (g.nodes[3].y - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pinned_nodes_survive_resampling() {
+        let mut circle = circular_graph(0.0, 0.0, 1.0, 40);
+        for n in &mut circle.nodes[5..10] {
+            n.pinned = true;
+        }
+        let g = resample(&circle, Resolution::NumPoints(80));
+        assert_eq!(g.nodes.len(), 80);
+        let positions = g.positions();
+        for old in &circle.nodes[5..10] {
+            let kept = &g.nodes[positions[&old.handle]];
+            assert!(kept.pinned && (kept.x, kept.y) == (old.x, old.y));
+        }
+        assert_eq!(g.nodes.iter().filter(|n| n.pinned).count(), 5);
+        assert!(g.nodes.iter().all(|n| n.pinned || n.handle.0 >= circle.handles_issued));
+    }
+
+    #[test]
+    fn spacing_picks_the_node_count() {
+        let circle = circular_graph(0.0, 0.0, 1.0, 500);
+        let g = resample(&circle, Resolution::Spacing(0.1));
+        assert_eq!(g.nodes.len(), (perimeter(&circle) / 0.1).round() as usize);
+        assert!((perimeter(&g) - perimeter(&circle)).abs() < 0.01);
+    }
+}
@@ -0,0 +1,120 @@
+/* Minimum clearance between edges, so folds can't press flat against each other or against the other layer.
+   Edges too close to their neighbors along the same layer don't count: two edges that close on the curve can only
+   be kept apart by straightening it. With a penalty the surface pays for every pair that's too close, without one
+   the move is refused. */
+use graph::spatial::{Segment, SegmentGrid};
+use graph::types::{Graph, ThickSurface};
+use graph::validate::Violation;
+use linalg_helpers::dist;
+use std::f64::consts::PI;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Separation {
+    pub min_distance: f64,
+    // None makes min_distance a hard constraint
+    pub penalty: Option<f64>,
+}
+
+// How far along the layer each node is from node 0, and the layer's length
+fn arc_positions(g: &Graph) -> (Vec<f64>, f64) {
+    let mut at = vec![0.0; g.nodes.len()];
+    let mut walked = 0.0;
+    let mut cur = &g.nodes[0];
+    loop {
+        let next = cur.next(g);
+        walked += dist(cur.x, cur.y, next.x, next.y);
+        if next.id == 0 {
+            break;
+        }
+        at[next.id] = walked;
+        cur = next;
+    }
+    (at, walked)
+}
+
+/* Two edges of a layer bent around a half circle min_distance wide are min_distance apart once they're
+   PI / 2 * min_distance apart along the layer, so only edges farther than that along the layer are held to it */
+fn far_along_the_layer(s1: &Segment, s2: &Segment, arcs: &[(Vec<f64>, f64)], min_distance: f64) -> bool {
+    if s1.layer != s2.layer {
+        return true;
+    }
+    let (at, length) = &arcs[s1.layer];
+    let forward = (at[s2.from] - at[s1.to]).rem_euclid(*length);
+    let backward = (at[s1.from] - at[s2.to]).rem_euclid(*length);
+    forward.min(backward) >= PI / 2.0 * min_distance
+}
+
+// Every pair of edges held to the minimum distance and closer than it, with how close they are
+pub fn too_close(ts: &ThickSurface, min_distance: f64) -> Vec<(Segment, Segment, f64)> {
+    let arcs: Vec<(Vec<f64>, f64)> = ts.layers.iter().map(arc_positions).collect();
+    SegmentGrid::new(&ts.layers, min_distance)
+        .close_pairs(min_distance)
+        .filter(|(s1, s2, _)| far_along_the_layer(s1, s2, &arcs, min_distance))
+        .collect()
+}
+
+// What a surface pays for its edges being too close: penalty * (1 - d / min_distance)^2 for every pair
+pub fn penalty(ts: &ThickSurface, separation: &Option<Separation>) -> f64 {
+    match separation {
+        Some(Separation {
+            min_distance,
+            penalty: Some(weight),
+        }) => too_close(ts, *min_distance)
+            .iter()
+            .map(|(_, _, d)| weight * (1.0 - d / min_distance).powf(2.0))
+            .sum(),
+        _ => 0.0,
+    }
+}
+
+// With the hard constraint, the closest pair of edges that breaks it
+pub fn violation(ts: &ThickSurface, separation: &Option<Separation>) -> Option<Violation> {
+    match separation {
+        Some(Separation { min_distance, penalty: None }) => too_close(ts, *min_distance)
+            .into_iter()
+            .min_by(|(_, _, d1), (_, _, d2)| d1.partial_cmp(d2).unwrap())
+            .map(|(s1, s2, d)| Violation::TooClose {
+                layers: (s1.layer, s2.layer),
+                distance: d,
+            }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use graph::types::{INNER, OUTER};
+
+    #[test]
+    fn a_thin_spot_is_too_close() {
+        let hard = Some(Separation {
+            min_distance: 0.05,
+            penalty: None,
+        });
+        // A fine circle is curved everywhere, but nothing is close except along the layers
+        let mut ts = circular_thick_surface(1.0, 0.3, 400);
+        assert_eq!(violation(&ts, &hard), None);
+
+        // Push the inner layer over until it almost touches the outer one
+        for n in &mut ts.layers[INNER].nodes {
+            n.x += 0.28;
+        }
+        match violation(&ts, &hard) {
+            Some(Violation::TooClose { layers, distance }) => {
+                assert_eq!(layers, (OUTER, INNER));
+                assert!(distance < 0.03);
+            }
+            other => panic!("expected the layers to be too close, got {:?}", other),
+        }
+
+        let soft = Some(Separation {
+            min_distance: 0.05,
+            penalty: Some(10.0),
+        });
+        assert_eq!(violation(&ts, &soft), None);
+        assert!(penalty(&ts, &soft) > 0.0);
+        assert_eq!(penalty(&circular_thick_surface(1.0, 0.3, 400), &soft), 0.0);
+    }
+}
@@ -0,0 +1,258 @@
+/* Uniform bucket grids over points and over edges, so "what's the closest point to here" and "which edges cross or
+   come close" don't have to look at every node or every pair of edges. */
+use std::collections::{HashMap, HashSet};
+
+use graph::types::{Graph, NodeIndex};
+use linalg_helpers::{dist, intersection, segment_distance};
+
+pub struct PointGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<(f64, f64)>>,
+}
+
+impl PointGrid {
+    pub fn new(points: &Vec<(f64, f64)>, cell_size: f64) -> PointGrid {
+        let mut grid = PointGrid {
+            cell_size,
+            cells: HashMap::new(),
+        };
+        for p in points {
+            let c = grid.cell_of(p.0, p.1);
+            grid.cells.entry(c).or_default().push(*p);
+        }
+        grid
+    }
+
+    // Cells about as big as the graph's average edge, so each one holds a handful of nodes
+    pub fn from_graph(g: &Graph) -> PointGrid {
+        let total: f64 = g.nodes.iter().map(|n| dist(n.x, n.y, n.next(g).x, n.next(g).y)).sum();
+        let cell_size = (total / g.nodes.len() as f64).max(1e-9);
+        PointGrid::new(&g.nodes.iter().map(|n| (n.x, n.y)).collect(), cell_size)
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> (i64, i64) {
+        ((x / self.cell_size).floor() as i64, (y / self.cell_size).floor() as i64)
+    }
+
+    /* Searches rings of cells around (x, y), stopping once the ring is farther away than the best distance so far.
+       Infinity if the grid is empty */
+    pub fn nearest_distance(&self, x: f64, y: f64) -> f64 {
+        if self.cells.is_empty() {
+            return f64::INFINITY;
+        }
+        let (ci, cj) = self.cell_of(x, y);
+        let mut best = f64::INFINITY;
+        let mut ring: i64 = 0;
+        let mut cells_seen = 0;
+        loop {
+            for i in ci - ring..ci + ring + 1 {
+                for j in cj - ring..cj + ring + 1 {
+                    if (i - ci).abs() != ring && (j - cj).abs() != ring {
+                        continue;
+                    }
+                    if let Some(ps) = self.cells.get(&(i, j)) {
+                        cells_seen += 1;
+                        for p in ps {
+                            best = best.min(dist(x, y, p.0, p.1));
+                        }
+                    }
+                }
+            }
+            // Anything in the next ring is at least ring * cell_size away
+            if best <= ring as f64 * self.cell_size || cells_seen == self.cells.len() {
+                return best;
+            }
+            ring += 1;
+        }
+    }
+}
+
+// An edge of one of the layers, from node `from` to its next node `to`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub layer: usize,
+    pub from: NodeIndex,
+    pub to: NodeIndex,
+    pub a: (f64, f64),
+    pub b: (f64, f64),
+}
+
+impl Segment {
+    fn shares_a_node_with(&self, other: &Segment) -> bool {
+        self.layer == other.layer && (self.from == other.from || self.to == other.from || self.from == other.to)
+    }
+
+    pub fn length(&self) -> f64 {
+        dist(self.a.0, self.a.1, self.b.0, self.b.1)
+    }
+
+    pub fn distance_to(&self, other: &Segment) -> f64 {
+        segment_distance((self.a.0, self.a.1, self.b.0, self.b.1), (other.a.0, other.a.1, other.b.0, other.b.1))
+    }
+
+    pub fn crossing(&self, other: &Segment) -> Option<(f64, f64)> {
+        intersection(self.a.0, self.a.1, self.b.0, self.b.1, other.a.0, other.a.1, other.b.0, other.b.1)
+    }
+}
+
+/* Every edge of every layer goes into the cells its bounding box, grown by reach / 2 on each side, covers. Two edges
+   closer than reach then always share a cell: the point halfway between their closest points is within reach / 2
+   of both. With reach 0 that's every pair of edges that cross */
+pub struct SegmentGrid {
+    pub segments: Vec<Segment>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl SegmentGrid {
+    pub fn new(layers: &[Graph], reach: f64) -> SegmentGrid {
+        let mut segments = Vec::new();
+        for (layer, g) in layers.iter().enumerate() {
+            for n in &g.nodes {
+                let next = n.next(g);
+                segments.push(Segment {
+                    layer,
+                    from: n.id,
+                    to: next.id,
+                    a: (n.x, n.y),
+                    b: (next.x, next.y),
+                });
+            }
+        }
+        SegmentGrid::of_segments(segments, reach)
+    }
+
+    // Layers that are still just points, each one closed back to its first point
+    pub fn from_points(point_layers: &[Vec<(f64, f64)>], reach: f64) -> SegmentGrid {
+        let mut segments = Vec::new();
+        for (layer, points) in point_layers.iter().enumerate() {
+            for i in 0..points.len() {
+                let j = (i + 1) % points.len();
+                segments.push(Segment {
+                    layer,
+                    from: i,
+                    to: j,
+                    a: points[i],
+                    b: points[j],
+                });
+            }
+        }
+        SegmentGrid::of_segments(segments, reach)
+    }
+
+    fn of_segments(segments: Vec<Segment>, reach: f64) -> SegmentGrid {
+        // Cells about as big as an average edge, but never smaller than the reach
+        let mean_length = segments.iter().map(|s| s.length()).sum::<f64>() / segments.len().max(1) as f64;
+        let cell_size = mean_length.max(reach).max(1e-9);
+        let cell_of = |x: f64, y: f64| ((x / cell_size).floor() as i64, (y / cell_size).floor() as i64);
+
+        let mut cells = HashMap::new();
+        let half = reach / 2.0;
+        for (k, s) in segments.iter().enumerate() {
+            let (lo_i, lo_j) = cell_of(s.a.0.min(s.b.0) - half, s.a.1.min(s.b.1) - half);
+            let (hi_i, hi_j) = cell_of(s.a.0.max(s.b.0) + half, s.a.1.max(s.b.1) + half);
+            for i in lo_i..hi_i + 1 {
+                for j in lo_j..hi_j + 1 {
+                    cells.entry((i, j)).or_insert_with(Vec::new).push(k);
+                }
+            }
+        }
+        SegmentGrid { segments, cells }
+    }
+
+    // Pairs of edges sharing a cell and not a node, each pair once and in the same order every time
+    fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = HashSet::new();
+        for ks in self.cells.values() {
+            for x in 0..ks.len() {
+                for y in x + 1..ks.len() {
+                    let (p, q) = (ks[x].min(ks[y]), ks[x].max(ks[y]));
+                    if !self.segments[p].shares_a_node_with(&self.segments[q]) {
+                        pairs.insert((p, q));
+                    }
+                }
+            }
+        }
+        let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+        pairs.sort();
+        pairs
+    }
+
+    // Every pair of edges that cross, and where. Only finds them all if the grid was built with any reach >= 0
+    pub fn crossings<'a>(&'a self) -> impl Iterator<Item = (Segment, Segment, (f64, f64))> + 'a {
+        self.candidate_pairs().into_iter().filter_map(move |(p, q)| {
+            let (s1, s2) = (self.segments[p], self.segments[q]);
+            s1.crossing(&s2).map(|at| (s1, s2, at))
+        })
+    }
+
+    // Every pair of edges closer than within (crossing ones at distance 0), within being at most the grid's reach
+    pub fn close_pairs<'a>(&'a self, within: f64) -> impl Iterator<Item = (Segment, Segment, f64)> + 'a {
+        self.candidate_pairs().into_iter().filter_map(move |(p, q)| {
+            let (s1, s2) = (self.segments[p], self.segments[q]);
+            let d = s1.distance_to(&s2);
+            if d < within {
+                Some((s1, s2, d))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/* Where two edges of the layers (of the same layer or not) cross, if they do. Only proper crossings count: edges that
+   just touch (an end on the other edge) or overlap along the same line don't, so a surface can be pressed flat against
+   itself without this noticing. A [separation] table is what keeps edges apart */
+pub fn first_crossing(layers: &[Graph]) -> Option<(f64, f64)> {
+    SegmentGrid::new(layers, 0.0).crossings().next().map(|(_, _, at)| at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_graph;
+    use linalg_helpers::lines_intersection;
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let points: Vec<(f64, f64)> = (0..200).map(|i| ((i as f64 * 0.37).sin() * 3.0, (i as f64 * 0.91).cos() * 2.0)).collect();
+        let grid = PointGrid::new(&points, 0.2);
+        for (x, y) in [(0.0, 0.0), (5.0, -4.0), (1.3, 0.7), (-20.0, 3.0)] {
+            let brute = points.iter().map(|p| dist(x, y, p.0, p.1)).fold(f64::INFINITY, f64::min);
+            assert_eq!(grid.nearest_distance(x, y), brute);
+        }
+    }
+
+    #[test]
+    fn crossings_match_brute_force() {
+        let mut layers = vec![circular_graph(0.0, 0.0, 1.0, 50), circular_graph(0.0, 0.0, 0.8, 40)];
+        assert_eq!(first_crossing(&layers), None);
+
+        // Poke one inner node out through the outer layer
+        layers[1].nodes[10].x *= 1.5;
+        layers[1].nodes[10].y *= 1.5;
+        assert!(first_crossing(&layers).is_some());
+        let every_edge: Vec<_> = layers.iter().flat_map(|g| g.nodes.iter().map(move |n| (n.x, n.y, n.next(g).x, n.next(g).y))).collect();
+        assert!(lines_intersection(&every_edge).is_some());
+
+        let points: Vec<Vec<(f64, f64)>> = layers.iter().map(|g| g.nodes.iter().map(|n| (n.x, n.y)).collect()).collect();
+        assert!(SegmentGrid::from_points(&points, 0.0).crossings().next().is_some());
+        let bowtie = vec![vec![(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0)]];
+        assert_eq!(SegmentGrid::from_points(&bowtie, 0.0).crossings().next().map(|(_, _, at)| at), Some((0.5, 0.5)));
+        // Touching isn't crossing
+        let touching = vec![vec![(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)], vec![(0.5, 0.0), (0.5, -1.0), (1.0, -1.0)]];
+        assert!(SegmentGrid::from_points(&touching, 0.0).crossings().next().is_none());
+    }
+
+    #[test]
+    fn close_pairs_skip_neighbors() {
+        let layers = vec![circular_graph(0.0, 0.0, 1.0, 50), circular_graph(0.0, 0.0, 0.9, 50)];
+        let grid = SegmentGrid::new(&layers, 0.15);
+        let close: Vec<(Segment, Segment, f64)> = grid.close_pairs(0.15).collect();
+        assert!(close.iter().all(|(s1, s2, _)| !s1.shares_a_node_with(s2)));
+        // Every outer edge has an inner edge right across it, about 0.1 away
+        let across: Vec<&(Segment, Segment, f64)> = close.iter().filter(|(s1, s2, _)| s1.layer != s2.layer).collect();
+        assert!(across.len() >= 50);
+        assert!(across.iter().all(|(_, _, d)| *d > 0.09));
+        assert_eq!(SegmentGrid::new(&layers, 0.05).close_pairs(0.05).count(), 0);
+    }
+}
@@ -1,6 +1,6 @@
 use graph::effects::{add_node_, merge_nodes_};
-use graph::{available_node_id, closest_node_across_all_layers, closest_nodes_across_all_layers, graphs_to_lines, NodeMerging};
-use linalg_helpers::lines_intersection;
+use graph::{available_node_id, closest_node_across_all_layers, closest_nodes_across_all_layers, make_counter_clockwise, NodeMerging};
+use graph::spatial::first_crossing;
 use std::collections::HashMap;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -26,6 +26,12 @@ impl Smooth<usize, f64> {
 }
 
 pub type NodeIndex = usize;
+
+/* A node's index moves around: deleting a node swaps the last one into its slot, and merges compact the vector.
+   A handle is given to a node when it's created and stays with it until it dies. Handles are never reused within
+   a graph, so one held after its node was merged away looks up to None instead of to whoever took the slot */
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeHandle(pub u64);
 #[derive(Debug)]
 pub enum NodeChangeMap {
     NCM(HashMap<usize, NodeChange>),
@@ -100,10 +106,13 @@ pub struct NodeAddition {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     pub id: NodeIndex,
+    pub handle: NodeHandle,
     pub x: f64,
     pub y: f64,
     pub next_id: NodeIndex,
     pub prev_id: NodeIndex,
+    // Never moved or merged, see graph::pins
+    pub pinned: bool,
 }
 
 impl Node {
@@ -140,9 +149,23 @@ pub struct NodeChange {
 #[derive(Debug, Clone)]
 pub struct Graph {
     pub nodes: Vec<Node>,
+    // How many handles this graph has given out; the next node gets NodeHandle(handles_issued)
+    pub(crate) handles_issued: u64,
 }
 
 impl Graph {
+    pub(crate) fn next_handle(&self) -> NodeHandle {
+        NodeHandle(self.handles_issued)
+    }
+    pub(crate) fn new_handle(&mut self) -> NodeHandle {
+        let h = self.next_handle();
+        self.handles_issued += 1;
+        h
+    }
+    // Where the node with each handle currently is. Handles of nodes that have been merged away aren't in it
+    pub fn positions(&self) -> HashMap<NodeHandle, NodeIndex> {
+        self.nodes.iter().map(|n| (n.handle, n.id)).collect()
+    }
     pub fn next(&self, id: usize) -> &Node {
         self.nodes[id].next(self)
     }
@@ -173,7 +196,9 @@ pub struct ThickSurface {
 }
 
 impl ThickSurface {
-    pub(crate) fn new(outer: Graph, inner: Graph) -> ThickSurface {
+    pub(crate) fn new(mut outer: Graph, mut inner: Graph) -> ThickSurface {
+        make_counter_clockwise(&mut outer);
+        make_counter_clockwise(&mut inner);
         ThickSurface { layers: vec![outer, inner] }
     }
     pub(crate) fn points_iter(&self, layer_id: usize) -> Vec<&Node> {
@@ -197,13 +222,15 @@ impl ThickSurface {
         let new_node_id = available_node_id(&new_ts.layers[layer_id]);
         let new_node = Node {
             id: new_node_id,
+            handle: new_ts.layers[layer_id].next_handle(),
             x: x,
             y: y,
             next_id: next.id,
             prev_id: prev.id,
+            pinned: prev.pinned && next.pinned,
         };
         add_node_(&mut new_ts, layer_id, &NodeAddition { n: new_node });
-        match lines_intersection(&graphs_to_lines(&new_ts.layers)) {
+        match first_crossing(&new_ts.layers) {
             Some(_) => Err(()),
             _ => {
                 *self = new_ts;
@@ -223,7 +250,7 @@ impl ThickSurface {
             survivor_y: dier.prev(&new_ts.layers[layer_id]).clone().y,
         };
         merge_nodes_(&mut new_ts, &m);
-        match lines_intersection(&graphs_to_lines(&new_ts.layers)) {
+        match first_crossing(&new_ts.layers) {
             Some(_) => Err(()),
             _ => {
                 *self = new_ts;
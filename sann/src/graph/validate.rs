@@ -0,0 +1,281 @@
+/* Invariants every ThickSurface is supposed to keep, checked all at once.
+   Geometric checks only run on layers whose links make sense, otherwise walking them isn't even possible. */
+use std::fmt;
+
+use std::collections::HashSet;
+
+use graph::types::{Graph, NodeHandle, ThickSurface, INNER, OUTER};
+use graph::spatial::SegmentGrid;
+use graph::{area, contains_point, is_counter_clockwise};
+use stitcher::types::Stitching;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    IdNotIndex { layer: usize, index: usize, id: usize },
+    // Two live nodes sharing a handle, or a node holding one its graph never gave out
+    DuplicateHandle { layer: usize, handle: NodeHandle },
+    UnissuedHandle { layer: usize, id: usize, handle: NodeHandle },
+    LinkOutOfRange { layer: usize, id: usize, link: usize },
+    AsymmetricLinks { layer: usize, id: usize },
+    // Walking next from node 0 came back after cycle_len nodes instead of visiting all of them
+    BrokenCycle { layer: usize, cycle_len: usize, num_nodes: usize },
+    // Layers are stored counter-clockwise, see cyclic_graph_from_coords
+    NotCounterClockwise { layer: usize, signed_area: f64 },
+    // Too few nodes, or no area left, to enclose anything
+    Collapsed { layer: usize, num_nodes: usize, signed_area: f64 },
+    SelfIntersection { layer: usize, at: (f64, f64) },
+    CrossIntersection { at: (f64, f64) },
+    // First inner node found outside the outer layer, and how many are outside in total
+    InnerOutsideOuter { id: usize, count: usize },
+    // Two edges that don't share a node closer than the minimum separation, see graph::separation
+    TooClose { layers: (usize, usize), distance: f64 },
+    // Stitches to merged away nodes are fine, stitches to nodes that never existed aren't
+    StitchToUnknownNode { outer: NodeHandle, inner: NodeHandle },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::IdNotIndex { layer, index, id } => write!(f, "layer {}: node at index {} has id {}", layer, index, id),
+            Violation::LinkOutOfRange { layer, id, link } => write!(f, "layer {}: node {} links to {}, which doesn't exist", layer, id, link),
+            Violation::AsymmetricLinks { layer, id } => write!(f, "layer {}: node {} isn't the prev of its next or the next of its prev", layer, id),
+            Violation::BrokenCycle { layer, cycle_len, num_nodes } => {
+                write!(f, "layer {}: the cycle through node 0 has {} nodes, the layer has {}", layer, cycle_len, num_nodes)
+            }
+            Violation::NotCounterClockwise { layer, signed_area } => {
+                write!(f, "layer {} isn't counter-clockwise (signed area {})", layer, signed_area)
+            }
+            Violation::Collapsed { layer, num_nodes, signed_area } => {
+                write!(f, "layer {} collapsed: {} nodes, signed area {}", layer, num_nodes, signed_area)
+            }
+            Violation::SelfIntersection { layer, at } => write!(f, "layer {} crosses itself at {:?}", layer, at),
+            Violation::CrossIntersection { at } => write!(f, "outer and inner layers cross at {:?}", at),
+            Violation::TooClose { layers, distance } => {
+                write!(f, "edges of layers {} and {} are only {} apart", layers.0, layers.1, distance)
+            }
+            Violation::InnerOutsideOuter { id, count } => write!(f, "{} inner nodes are outside the outer layer, e.g. node {}", count, id),
+            Violation::DuplicateHandle { layer, handle } => write!(f, "layer {}: more than one node has handle {:?}", layer, handle),
+            Violation::UnissuedHandle { layer, id, handle } => write!(f, "layer {}: node {} has handle {:?}, which was never given out", layer, id, handle),
+            Violation::StitchToUnknownNode { outer, inner } => {
+                write!(f, "stitching pairs outer {:?} with inner {:?}, one of them was never a node", outer, inner)
+            }
+        }
+    }
+}
+
+fn structure_violations(g: &Graph, layer: usize) -> Vec<Violation> {
+    let mut ret = Vec::new();
+    let len = g.nodes.len();
+    let mut handles = HashSet::new();
+    for (index, n) in g.nodes.iter().enumerate() {
+        if n.id != index {
+            ret.push(Violation::IdNotIndex { layer, index, id: n.id });
+        }
+        if !handles.insert(n.handle) {
+            ret.push(Violation::DuplicateHandle { layer, handle: n.handle });
+        }
+        if n.handle >= g.next_handle() {
+            ret.push(Violation::UnissuedHandle { layer, id: n.id, handle: n.handle });
+        }
+        for link in [n.next_id, n.prev_id] {
+            if link >= len {
+                ret.push(Violation::LinkOutOfRange { layer, id: n.id, link });
+            }
+        }
+    }
+    if !ret.is_empty() {
+        return ret;
+    }
+
+    for n in &g.nodes {
+        if n.next(g).prev_id != n.id || n.prev(g).next_id != n.id {
+            ret.push(Violation::AsymmetricLinks { layer, id: n.id });
+        }
+    }
+    let mut cur = &g.nodes[0];
+    let mut cycle_len = 1;
+    while cur.next_id != 0 && cycle_len <= len {
+        cur = cur.next(g);
+        cycle_len += 1;
+    }
+    if cycle_len != len {
+        ret.push(Violation::BrokenCycle { layer, cycle_len, num_nodes: len });
+    }
+    ret
+}
+
+fn inner_outside_outer(outer: &Graph, inner: &Graph) -> Option<Violation> {
+    let outside: Vec<usize> = inner.nodes.iter().filter(|n| !contains_point(outer, n.x, n.y)).map(|n| n.id).collect();
+    if outside.is_empty() {
+        None
+    } else {
+        Some(Violation::InnerOutsideOuter {
+            id: outside[0],
+            count: outside.len(),
+        })
+    }
+}
+
+/* What every accepted proposal and every imported surface has to satisfy for the gray matter to be an annulus-like
+   region: no layer collapsed, no crossings, and the inner layer inside the outer one. Only returns the first problem,
+   since it runs on every step. Without crossings, a single inner node inside the outer layer means all of it is */
+pub fn containment(ts: &ThickSurface) -> Option<Violation> {
+    for (layer, g) in ts.layers.iter().enumerate() {
+        if g.nodes.len() < 3 || area(g) <= 0.0 {
+            return Some(Violation::Collapsed {
+                layer,
+                num_nodes: g.nodes.len(),
+                signed_area: area(g),
+            });
+        }
+    }
+    if let Some((s1, s2, at)) = SegmentGrid::new(&ts.layers, 0.0).crossings().next() {
+        return Some(if s1.layer == s2.layer {
+            Violation::SelfIntersection { layer: s1.layer, at }
+        } else {
+            Violation::CrossIntersection { at }
+        });
+    }
+    if ts.layers.len() > INNER {
+        let (outer, inner) = (&ts.layers[OUTER], &ts.layers[INNER]);
+        if !contains_point(outer, inner.nodes[0].x, inner.nodes[0].y) {
+            return inner_outside_outer(outer, inner);
+        }
+    }
+    None
+}
+
+pub fn validate(ts: &ThickSurface) -> Vec<Violation> {
+    let mut ret = Vec::new();
+    let mut walkable = true;
+    for (layer, g) in ts.layers.iter().enumerate() {
+        let mut vs = structure_violations(g, layer);
+        walkable = walkable && vs.is_empty();
+        ret.append(&mut vs);
+    }
+    if !walkable {
+        return ret;
+    }
+
+    for (layer, g) in ts.layers.iter().enumerate() {
+        if g.nodes.len() < 3 {
+            ret.push(Violation::Collapsed {
+                layer,
+                num_nodes: g.nodes.len(),
+                signed_area: area(g),
+            });
+        } else if !is_counter_clockwise(g) {
+            ret.push(Violation::NotCounterClockwise { layer, signed_area: area(g) });
+        }
+    }
+    // The first crossing of each layer with itself, and the first one between layers
+    let mut crossed = vec![false; ts.layers.len() + 1];
+    for (s1, s2, at) in SegmentGrid::new(&ts.layers, 0.0).crossings() {
+        let (slot, v) = if s1.layer == s2.layer {
+            (s1.layer, Violation::SelfIntersection { layer: s1.layer, at })
+        } else {
+            (ts.layers.len(), Violation::CrossIntersection { at })
+        };
+        if !crossed[slot] {
+            crossed[slot] = true;
+            ret.push(v);
+        }
+    }
+    if ts.layers.len() > INNER {
+        let (outer, inner) = (&ts.layers[OUTER], &ts.layers[INNER]);
+        if let Some(v) = inner_outside_outer(outer, inner) {
+            ret.push(v);
+        }
+    }
+    ret
+}
+
+pub fn validate_stitching(ts: &ThickSurface, s: &Stitching) -> Vec<Violation> {
+    s.pairs()
+        .iter()
+        .filter(|(o, i)| *o >= ts.layers[OUTER].next_handle() || *i >= ts.layers[INNER].next_handle())
+        .map(|(o, i)| Violation::StitchToUnknownNode { outer: *o, inner: *i })
+        .collect()
+}
+
+pub fn validate_all(ts: &ThickSurface, s: &Stitching) -> Vec<Violation> {
+    let mut ret = validate(ts);
+    ret.append(&mut validate_stitching(ts, s));
+    ret
+}
+
+pub fn report(operation: &str, timestep: u64, violations: &Vec<Violation>) -> String {
+    let mut ret = format!("Surface broken by {} at step {}:", operation, timestep);
+    for v in violations {
+        ret.push_str(&format!("\n  - {}", v));
+    }
+    ret
+}
+
+// For debug_checks: stops the run as soon as an operation leaves the surface in a bad state, saying which one
+pub fn check_after(operation: &str, timestep: u64, ts: &ThickSurface, s: &Stitching) {
+    let violations = validate_all(ts, s);
+    if !violations.is_empty() {
+        panic!("{}", report(operation, timestep, &violations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::circular_thick_surface;
+    use stitcher::stitch_default;
+
+    #[test]
+    fn fresh_surface_is_valid() {
+        let ts = circular_thick_surface(1.0, 0.3, 40);
+        assert_eq!(validate_all(&ts, &stitch_default(&ts)), vec![]);
+    }
+
+    #[test]
+    fn broken_surfaces_are_reported() {
+        let mut ts = circular_thick_surface(1.0, 0.3, 40);
+        ts.layers[INNER].nodes[5].x = 2.0;
+        let vs = validate(&ts);
+        assert!(vs.iter().any(|v| matches!(v, Violation::CrossIntersection { .. })));
+        assert!(vs.contains(&Violation::InnerOutsideOuter { id: 5, count: 1 }));
+
+        let mut ts = circular_thick_surface(1.0, 0.3, 40);
+        ts.layers[OUTER].nodes[3].next_id = 7;
+        let vs = validate(&ts);
+        assert!(vs.contains(&Violation::AsymmetricLinks { layer: OUTER, id: 3 }));
+        assert!(vs.contains(&Violation::BrokenCycle {
+            layer: OUTER,
+            cycle_len: 37,
+            num_nodes: 40
+        }));
+    }
+
+    #[test]
+    fn containment_needs_the_inner_layer_inside() {
+        assert_eq!(containment(&circular_thick_surface(1.0, 0.3, 40)), None);
+
+        // Swapped layers don't cross, but the "inner" one is now the big one
+        let ts = circular_thick_surface(1.0, 0.3, 40);
+        let swapped = ThickSurface {
+            layers: vec![ts.layers[INNER].clone(), ts.layers[OUTER].clone()],
+        };
+        assert_eq!(containment(&swapped), Some(Violation::InnerOutsideOuter { id: 0, count: 40 }));
+
+        // Entirely outside, off to the side
+        let mut away = circular_thick_surface(1.0, 0.3, 40);
+        for n in &mut away.layers[INNER].nodes {
+            n.x += 5.0;
+        }
+        assert_eq!(containment(&away), Some(Violation::InnerOutsideOuter { id: 0, count: 40 }));
+
+        let mut flat = circular_thick_surface(1.0, 0.3, 40);
+        for n in &mut flat.layers[INNER].nodes {
+            n.y = 0.0;
+        }
+        match containment(&flat) {
+            Some(Violation::Collapsed { layer, .. }) => assert_eq!(layer, INNER),
+            other => panic!("expected a collapsed inner layer, got {:?}", other),
+        }
+    }
+}
@@ -46,6 +46,28 @@ pub fn bisecting_vector(middle_x: f64, middle_y: f64, clkwise_x: f64, clkwise_y:
     }
 }
 
+/* Shoelace formula over a closed polygon given by its vertices in order.
+Positive if they go around counter-clockwise, negative if clockwise. */
+pub fn signed_area(points: &[(f64, f64)]) -> f64 {
+    let mut ret = 0.0;
+    for i in 0..points.len() {
+        let ((x1, y1), (x2, y2)) = (points[i], points[(i + 1) % points.len()]);
+        ret = ret + x1 * y2 - x2 * y1;
+    }
+    ret / 2.0
+}
+
+/* Distance from (px, py) to the closest point of the segment (x1, y1) -> (x2, y2) */
+pub fn dist_to_segment(px: f64, py: f64, x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let len_sq = dx * dx + dy * dy;
+    if len_sq == 0.0 {
+        return dist(px, py, x1, y1);
+    }
+    let t = (((px - x1) * dx + (py - y1) * dy) / len_sq).clamp(0.0, 1.0);
+    dist(px, py, x1 + t * dx, y1 + t * dy)
+}
+
 /* 0,0 -> x1, y1, 0,0 -> x2,y2 vector cross product.
 Positive if (0,0)->1->2 is a counter-clockwise turn, negative if clockwise, 0 if collinear. */
 fn cross_product(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
@@ -53,7 +75,7 @@ fn cross_product(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
 }
 
 /* Returns potential intersection between lines (x1 y1, x2 y2) and (x3 y3, x4 y4) */
-fn intersection(x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, x4: f64, y4: f64) -> Option<(f64, f64)> {
+pub fn intersection(x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, x4: f64, y4: f64) -> Option<(f64, f64)> {
     let (rx, ry, sx, sy) = (x2 - x1, y2 - y1, x4 - x3, y4 - y3);
 
     /* Now we have: line = q + qv, and any point on the vector is obtainable by p + t*r, for some t
@@ -74,19 +96,15 @@ fn intersection(x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, x4: f64, y
     }
 }
 
-pub fn points_to_cyclic_lines(point_layers: &Vec<Vec<(f64, f64)>>) -> Vec<(f64, f64, f64, f64)> {
-    let mut ret = Vec::new();
-    for layer in point_layers {
-        // Disgusting if :sob:
-        if layer.len() >= 2 {
-            let prev = layer[0];
-            for i in 1..layer.len() + 1 {
-                let ind = i % layer.len();
-                ret.push((prev.0, prev.1, layer[ind].0, layer[ind].1));
-            }
-        }
+// Distance between the closest points of two segments (x1, y1, x2, y2), 0 if they cross
+pub fn segment_distance((x1, y1, x2, y2): (f64, f64, f64, f64), (x3, y3, x4, y4): (f64, f64, f64, f64)) -> f64 {
+    if intersection(x1, y1, x2, y2, x3, y3, x4, y4).is_some() {
+        return 0.0;
     }
-    ret
+    dist_to_segment(x1, y1, x3, y3, x4, y4)
+        .min(dist_to_segment(x2, y2, x3, y3, x4, y4))
+        .min(dist_to_segment(x3, y3, x1, y1, x2, y2))
+        .min(dist_to_segment(x4, y4, x1, y1, x2, y2))
 }
 
 pub fn closest_point(point_layers: &Vec<Vec<(f64, f64)>>, point_x: f64, point_y: f64) -> Option<(f64, f64)> {
@@ -111,11 +129,43 @@ pub fn closest_point(point_layers: &Vec<Vec<(f64, f64)>>, point_x: f64, point_y:
     //     .min_by(point_cmp)
 }
 
-pub fn lines_intersection(lines: &Vec<(f64, f64, f64, f64)>) -> Option<(f64, f64)> {
-    for i in 0..(if lines.len() > 0 {lines.len() - 1} else { 0 }) {
-        let (x1, y1, x2, y2) = lines[i];
-        for j in i..lines.len() - 1 {
-            let (x3, y3, x4, y4) = lines[j];
+/* Same as numpy's logspace: n values from 10^low_exp to 10^high_exp, evenly spaced in log scale */
+pub fn log_spaced(low_exp: f64, high_exp: f64, n: usize) -> Vec<f64> {
+    if n == 1 {
+        return vec![10.0_f64.powf(low_exp)];
+    }
+    (0..n)
+        .map(|i| 10.0_f64.powf(low_exp + (high_exp - low_exp) * i as f64 / (n - 1) as f64))
+        .collect()
+}
+
+/* Least squares fit of ys = slope * xs + intercept. Returns (slope, intercept, r_squared) */
+pub fn linear_fit(xs: &[f64], ys: &[f64]) -> (f64, f64, f64) {
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (mut s_xy, mut s_xx) = (0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        s_xy += (x - mean_x) * (y - mean_y);
+        s_xx += (x - mean_x) * (x - mean_x);
+    }
+    let slope = if s_xx == 0.0 { 0.0 } else { s_xy / s_xx };
+    let intercept = mean_y - slope * mean_x;
+
+    let (mut ss_res, mut ss_tot) = (0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        ss_res += (y - (slope * x + intercept)).powf(2.0);
+        ss_tot += (y - mean_y).powf(2.0);
+    }
+    let r_squared = if ss_tot == 0.0 { 1.0 } else { 1.0 - ss_res / ss_tot };
+    (slope, intercept, r_squared)
+}
+
+// Every pair of lines, the last one included. Only the tests still check it against graph::spatial::first_crossing
+#[cfg(test)]
+pub fn lines_intersection(lines: &[(f64, f64, f64, f64)]) -> Option<(f64, f64)> {
+    for (i, &(x1, y1, x2, y2)) in lines.iter().enumerate() {
+        for &(x3, y3, x4, y4) in &lines[i + 1..] {
             match intersection(x1, y1, x2, y2, x3, y3, x4, y4) {
                 Some(int) => return Some(int),
                 _ => continue,
@@ -164,4 +214,21 @@ mod tests {
             None => assert!(true),
         }
     }
+
+    #[test]
+    fn lines_intersection_checks_the_last_line() {
+        let lines = vec![(0.0, 0.0, 1.0, 0.0), (5.0, 5.0, 6.0, 6.0), (0.5, -1.0, 0.5, 1.0)];
+        assert_eq!(lines_intersection(&lines), Some((0.5, 0.0)));
+        assert_eq!(segment_distance((0.0, 0.0, 1.0, 0.0), (0.5, 0.25, 0.5, 1.0)), 0.25);
+    }
+
+    #[test]
+    fn linear_fit_recovers_a_line() {
+        let xs = vec![0.0, 1.0, 2.0, 3.0];
+        let ys: Vec<f64> = xs.iter().map(|x| 1.5 * x - 2.0).collect();
+        let (slope, intercept, r_squared) = linear_fit(&xs, &ys);
+        assert!(approx_eq!(f64, slope, 1.5, epsilon = 1e-12));
+        assert!(approx_eq!(f64, intercept, -2.0, epsilon = 1e-12));
+        assert!(approx_eq!(f64, r_squared, 1.0, epsilon = 1e-12));
+    }
 }
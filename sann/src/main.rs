@@ -1,5 +1,6 @@
 #![recursion_limit = "256"]
 
+mod analysis;
 mod file_io;
 mod graph;
 mod linalg_helpers;
@@ -25,11 +26,17 @@ extern crate conrod_core;
 extern crate conrod_piston;
 extern crate find_folder;
 extern crate geo;
+extern crate gif;
+extern crate geojson;
 extern crate num_traits;
 extern crate piston_window;
+extern crate png;
+extern crate crc32fast;
 extern crate regex;
+extern crate wkt;
 
 use renderer::draw_mode::draw_mode_rendering;
+use std::collections::BTreeSet;
 use std::env;
 use simulated_annealing::energy;
 use graph::area;
@@ -42,11 +49,8 @@ use num_traits::Pow;
 
 extern crate csv;
 
-use std::error::Error;
 use std::io;
 use csv::Writer;
-use std::fs::File;
-use std::io::prelude::*;
 
 extern crate lexical;
 
@@ -55,16 +59,29 @@ fn real_main() {
         Err(_) => panic!("No parameters.toml file found in directory"),
         Ok(content) => file_io::toml_table_to_params(content.parse::<toml::Value>().unwrap()),
     };
+    let effective_params = write_effective_params(&params, &file_io::params::effective_params_path(&params.output_file_path));
     let (mut renderer, mut window) = renderer::setup_renderer();
     let mut sim_state = simulated_annealing::SimState::initial_state(&params);
+    let manifest_path = file_io::manifest::manifest_path(&params.output_file_path);
+    let mut manifest = file_io::manifest::Manifest::start(&params, STEPPER, sim_state.seed);
+    write_manifest(&manifest, &manifest_path);
 
-    renderer::setup_optimization_and_loop(
+    let recording_state = renderer::setup_optimization_and_loop(
         &mut sim_state,
         &mut window,
         &mut renderer,
         |ss| renderer::lines_from_thick_surface(&ss.ts),
         &params,
-    )
+    );
+    // Resetting (R) starts the run over, with a new seed unless params has one
+    manifest.seed = sim_state.seed;
+    let reason = if sim_state.timestep >= renderer::MAX_STEPS {
+        format!("reached the GUI's {} steps", renderer::MAX_STEPS)
+    } else {
+        String::from("the window was closed")
+    };
+    let files = Some(params.output_file_path.clone()).into_iter().chain(effective_params).collect();
+    finish_manifest(&mut manifest, &sim_state, &recording_state, &reason, files, &manifest_path);
 }
 
 fn no_gui_main(params_file_path: &str
@@ -76,13 +93,18 @@ fn no_gui_main(params_file_path: &str
         Err(_) => panic!(format!("Parameter file named \"{}\" not found.", params_file_path)),
         Ok(content) => file_io::toml_table_to_params(content.parse::<toml::Value>().unwrap()),
     };
+    let effective_params = write_effective_params(&params, &file_io::params::effective_params_path(&params.output_file_path));
     
    
-    let mut recording_state = recorders::RecordingState::initial_state(&params).unwrap_or_else(|| {panic!("Couldn't create recording state")});
+    let mut recording_state = recorders::RecordingState::initial_state(&params, &no_gui_cadence()).unwrap_or_else(|err| {panic!("Couldn't create the recorders' file \"{}\": {}", params.output_file_path, err)});
     let mut sim_state = simulated_annealing::SimState::initial_state(&params);
+    let manifest_path = file_io::manifest::manifest_path(&params.output_file_path);
+    let mut manifest = file_io::manifest::Manifest::start(&params, STEPPER, sim_state.seed);
+    write_manifest(&manifest, &manifest_path);
         
     loop {
         simulated_annealing_dumber_and_better::step(&mut sim_state, &params);
+        record_step(&sim_state, &params, &mut recording_state, how_many_reps);
         
         if sim_state.timestep % 1000000 == 0 {
         
@@ -95,11 +117,6 @@ EXP = {:?} ",graph::graph_to_points(&sim_state.ts.layers[0]),
  );
     
 
-  
-  	
-    
-            recorders::record(&sim_state, &params, &mut recording_state);
-        
         }
         
         
@@ -118,10 +135,8 @@ EXP = {:?} ",graph::graph_to_points(&sim_state.ts.layers[0]),
  );
     
 
-  
-  	
-    
-            recorders::record(&sim_state, &params, &mut recording_state);
+            let files = Some(params.output_file_path.clone()).into_iter().chain(effective_params.clone()).collect();
+            finish_manifest(&mut manifest, &sim_state, &recording_state, &reached(how_many_reps), files, &manifest_path);
                
             break;            
         	}
@@ -131,103 +146,167 @@ EXP = {:?} ",graph::graph_to_points(&sim_state.ts.layers[0]),
     }
 
 
-fn create_csv_out(matrix: Vec<(f64, f64)>, output: &str) -> Result<(), Box<dyn Error>> {
-
-    let file_path = format!("{}/dados_out.csv",output);
-    let mut file = File::create(file_path)?;
-
-    // Escrever cabeçalho
-    writeln!(file, "Componente1,Componente2")?;
-
-    // Escrever dados da matriz
-    for (comp1, comp2) in matrix {
-        writeln!(file, "{},{}", comp1, comp2)?;
+// Every step goes to the recorders, which write a row only when one of their cadences is due; the last step is the end
+fn record_step(sim_state: &simulated_annealing::SimState, params: &types::Params, recording_state: &mut recorders::RecordingState, how_many_reps: u64) {
+    if sim_state.timestep == how_many_reps {
+        recorders::record_end(sim_state, params, recording_state);
+    } else {
+        recorders::record(sim_state, params, recording_state);
     }
-
-    println!("Arquivo CSV gerado com sucesso!");
-
-    Ok(())
 }
 
-fn create_csv_in(matrix: Vec<(f64, f64)>, output: &str) -> Result<(), Box<dyn Error>> {
-    
-    let file_path = format!("{}/dados_in.csv",output);
-    let mut file = File::create(file_path)?;
-
-    // Escrever cabeçalho
-    writeln!(file, "Componente1,Componente2")?;
-
-    // Escrever dados da matriz
-    for (comp1, comp2) in matrix {
-        writeln!(file, "{},{}", comp1, comp2)?;
+// What recorders given by name alone record on in no_gui, as they always have: every 1000000 steps and the last one.
+// coord and the GUI record them every step
+fn no_gui_cadence() -> recorders::Cadence {
+    recorders::Cadence {
+        on: vec![recorders::RecordEvent::End],
+        ..recorders::Cadence::every(1000000)
     }
+}
 
-    println!("Arquivo CSV gerado com sucesso!");
-
-    Ok(())
+// How coord and no_gui stop, the only way they do
+fn reached(how_many_reps: u64) -> String {
+    format!("reached the requested {} steps", how_many_reps)
 }
 
-fn create_csv_ext(matrix: Vec<(f64, f64)>, output: &str) -> Result<(), Box<dyn Error>> {
+// What coord, no_gui and the GUI step the simulation with, for their manifests
+const STEPPER: &str = "simulated_annealing_dumber_and_better::step";
 
-    let file_path = format!("{}/dados_ext.csv", output);
-    let mut file = File::create(file_path)?;
+fn write_manifest(manifest: &file_io::manifest::Manifest, file_path: &str) {
+    if let Err(err) = manifest.write(file_path) {
+        eprintln!("Erro ao salvar o manifesto: {}", err);
+    }
+}
 
+fn finish_manifest(
+    manifest: &mut file_io::manifest::Manifest,
+    sim_state: &simulated_annealing::SimState,
+    recording_state: &recorders::RecordingState,
+    stopping_reason: &str,
+    files: Vec<String>,
+    file_path: &str,
+) {
+    let final_recorders = recording_state.columns().into_iter().zip(recording_state.last_recorded.iter().cloned()).collect();
+    manifest.finish(sim_state.timestep, stopping_reason, final_recorders, files);
+    write_manifest(manifest, file_path);
+}
 
-    // Escrever cabeçalho
-    writeln!(file, "Componente1,Componente2")?;
+// Returns the file if it got written, like the other writers here, for the manifest
+fn write_effective_params(params: &types::Params, file_path: &str) -> Option<String> {
+    match file_io::params::write_params(params, file_path) {
+        Ok(()) => Some(String::from(file_path)),
+        Err(err) => {
+            eprintln!("Erro ao salvar os parâmetros: {}", err);
+            None
+        }
+    }
+}
 
-    // Escrever dados da matriz
-    for (comp1, comp2) in matrix {
-        writeln!(file, "{},{}", comp1, comp2)?;
+// What gets written to the CSVs: the surface itself, or an evenly resampled copy if export_num_points is set
+fn surface_to_export(ts: &graph::types::ThickSurface, params: &types::Params) -> graph::types::ThickSurface {
+    match params.export_num_points {
+        Some(n) => graph::resample::resample_thick_surface(ts, graph::resample::Resolution::NumPoints(n)),
+        None => ts.clone(),
     }
+}
 
-    println!("Arquivo CSV gerado com sucesso!");
+// A snapshot for this timestep, and dados_*.csv refreshed to the same surface
+fn write_snapshot(ts: &graph::types::ThickSurface, params: &types::Params, timestep: u64, snapshots: &file_io::snapshots::SnapshotWriter, output: &str) -> Vec<String> {
+    let exported = surface_to_export(ts, params);
+    let mut files = match snapshots.write(&exported, timestep) {
+        Ok(path) => vec![path],
+        Err(err) => {
+            eprintln!("Erro ao criar o arquivo CSV: {}", err);
+            vec![]
+        }
+    };
+    files.extend(write_latest(&exported, output));
+    files
+}
 
-    Ok(())
+fn write_latest(ts: &graph::types::ThickSurface, output: &str) -> Vec<String> {
+    file_io::snapshots::write_latest(ts, output).unwrap_or_else(|err| {
+        eprintln!("Erro ao criar o arquivo CSV: {}", err);
+        vec![]
+    })
 }
 
+fn write_thickness_histogram(thicknesses: &[f64], file_path: &str) -> Option<String> {
+    match analysis::thickness::write_histogram_csv(thicknesses, THICKNESS_BINS, file_path) {
+        Ok(()) => Some(String::from(file_path)),
+        Err(err) => {
+            eprintln!("Erro ao criar o arquivo CSV: {}", err);
+            None
+        }
+    }
+}
 
+const THICKNESS_BINS: usize = 30;
 
 fn coord_main(params_file_path: &str
     , how_many_reps: u64, output: &str) 
     
     {
     
-    let params: types::Params = match std::fs::read_to_string(params_file_path) {
+    let params_table: toml::Value = match std::fs::read_to_string(params_file_path) {
         Err(_) => panic!(format!("Parameter file named \"{}\" not found.", params_file_path)),
-        Ok(content) => file_io::toml_table_to_params(content.parse::<toml::Value>().unwrap()),
+        Ok(content) => content.parse::<toml::Value>().unwrap(),
     };
+    let params: types::Params = file_io::toml_table_to_params(params_table);
+    let mut animator = params.animation.as_ref().map(|ap| {
+        file_io::animation::Animator::create(output, ap).unwrap_or_else(|err| {panic!("Couldn't create the animation: {}", err)})
+    });
+    let trajectory_path = format!("{}/trajectory.bin", output);
+    let mut trajectory = params.trajectory.as_ref().map(|tp| {
+        file_io::trajectory::TrajectoryWriter::create(&trajectory_path, tp).unwrap_or_else(|err| {panic!("Couldn't create the trajectory: {}", err)})
+    });
+    // Every file the run writes, for the manifest: the recorders' CSV wherever output_file_path put it and the rest in output
+    let mut files: BTreeSet<String> = write_effective_params(&params, &format!("{}/effective_params.toml", output)).into_iter().collect();
+    if trajectory.is_some() {
+        files.insert(trajectory_path.clone());
+    }
     
    
-    let mut recording_state = recorders::RecordingState::initial_state(&params).unwrap_or_else(|| {panic!("Couldn't create recording state")});
+    let mut recording_state = recorders::RecordingState::initial_state(&params, &recorders::Cadence::every(1)).unwrap_or_else(|err| {panic!("Couldn't create the recorders' file \"{}\": {}", params.output_file_path, err)});
+    files.insert(params.output_file_path.clone());
     let mut sim_state = simulated_annealing::SimState::initial_state(&params);
+    let snapshots = file_io::snapshots::SnapshotWriter::create(output, params.snapshot_layout).unwrap_or_else(|err| {panic!("Couldn't create the snapshot file: {}", err)});
+    let manifest_path = format!("{}/manifest.toml", output);
+    let mut manifest = file_io::manifest::Manifest::start(&params, STEPPER, sim_state.seed);
+    write_manifest(&manifest, &manifest_path);
     
-    let mut sum_E = 0.0;
-    let mut sum_E_2 = 0.0;
-    
-    let mut sum_K = 0.0;
-    let mut sum_K_2 = 0.0;
+    // Means over the whole run, for the convergence check below
+    let mut energy_stats = recorders::RunningStats::of("energy", recorders::energy);
+    let mut k_stats = recorders::RunningStats::of("k", recorders::k);
     
         
     loop {
         simulated_annealing_dumber_and_better::step(&mut sim_state, &params);
-        recorders::record(&sim_state, &params, &mut recording_state);
+        record_step(&sim_state, &params, &mut recording_state, how_many_reps);
+        if let Some(t) = &mut trajectory {
+            if t.wants(sim_state.timestep) {
+                if let Err(err) = t.record(&sim_state.ts, sim_state.timestep) {
+                    eprintln!("Erro ao gravar a trajetória: {}", err);
+                }
+            }
+        }
+        if let Some(a) = &mut animator {
+            if a.wants(sim_state.timestep) {
+                if let Err(err) = a.add_frame(&sim_state.ts) {
+                    eprintln!("Erro ao salvar o quadro da animação: {}", err);
+                }
+            }
+        }
         
-        let K = recorders::k(&sim_state.ts, &params);
-        let E = recorders::energy(&sim_state.ts, &params);
+        let energy = recorders::energy(&sim_state.ts, &params);
+        let k = recorders::k(&sim_state.ts, &params);
+        energy_stats.add(energy);
+        k_stats.add(k);
+        // Not the variance of the run: this step's squared distance from the mean so far, over the number of steps,
+        // which goes to 0 as K settles
         let t = sim_state.timestep as f64;
-        
-        sum_E += E;
-        let media_E = sum_E/t;
-        let E_2 = (E - media_E)*(E - media_E);        
-        sum_E_2 += E_2 ;
-        let var_E = E_2/t;
-        
-        sum_K += K;
-        let media = sum_K/t;
-        let K_2 = (K - media)*(K - media);        
-        sum_K_2 += K_2 ;
-        let var = K_2/t;
+        let var_e = (energy - energy_stats.mean()).powi(2) / t;
+        let var = (k - k_stats.mean()).powi(2) / t;
         
         
         if sim_state.timestep % 100 == 0 {
@@ -235,33 +314,42 @@ fn coord_main(params_file_path: &str
         
         println!("step: {:?}",var);
         
-        println!("step: {:?}",var_E);
+        println!("step: {:?}",var_e);
         
         
         
         }
         
         if sim_state.timestep == how_many_reps { // Não sei de onde tirar esse número
-    let matrix_out = graph::graph_to_points(&sim_state.ts.layers[0]);
-    let matrix_in = graph::graph_to_points(&sim_state.ts.layers[1]);
-    let matrix_ext = graph::graph_to_points(&convex_hull_from_graph(&sim_state.ts.layers[0]));
-
-    // Chamar a função para criar o arquivo CSV
-    if let Err(err) = create_csv_out(matrix_out,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }	
-    if let Err(err) = create_csv_in(matrix_in,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-    if let Err(err) = create_csv_ext(matrix_ext,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-            recorders::record(&sim_state, &params, &mut recording_state);
+    files.extend(write_snapshot(&sim_state.ts, &params, sim_state.timestep, &snapshots, output));
+            if let Some(hp) = &params.heat {
+                files.extend(heat_analysis(&sim_state.ts, hp, output));
+            }
+            if let Some(sp) = &params.svg {
+                files.extend(write_figure(&sim_state.ts, Some(&sim_state.stitching), sp, &format!("{}/surface.svg", output)));
+            }
+            let thicknesses = analysis::thickness::from_nearest_points(&sim_state.ts);
+            files.extend(write_thickness_histogram(&thicknesses, &format!("{}/thickness_hist.csv", output)));
+            // The same, measured along the stitching instead, which a finished run's CSVs don't have
+            let stitched = analysis::thickness::from_stitching(&sim_state.ts, &sim_state.stitching);
+            files.extend(write_thickness_histogram(&stitched, &format!("{}/thickness_stitched_hist.csv", output)));
+            if let Some(t) = &mut trajectory {
+                if let Err(err) = t.flush() {
+                    eprintln!("Erro ao gravar a trajetória: {}", err);
+                }
+            }
+            if let Some(a) = &mut animator {
+                if let Err(err) = a.finish() {
+                    eprintln!("Erro ao salvar a animação: {}", err);
+                }
+                files.extend(a.files());
+            }
+            finish_manifest(&mut manifest, &sim_state, &recording_state, &reached(how_many_reps), files.into_iter().collect(), &manifest_path);
         
         break;            
         	}
         
-        if sim_state.timestep % 1000 == 0 {
+        if sim_state.timestep.is_multiple_of(params.snapshot_every) {
         
         
     
@@ -269,21 +357,7 @@ fn coord_main(params_file_path: &str
   
   		
     
-    let matrix_out = graph::graph_to_points(&sim_state.ts.layers[0]);
-    let matrix_in = graph::graph_to_points(&sim_state.ts.layers[1]);
-    let matrix_ext = graph::graph_to_points(&convex_hull_from_graph(&sim_state.ts.layers[0]));
-
-    // Chamar a função para criar o arquivo CSV
-    if let Err(err) = create_csv_out(matrix_out,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }	
-    if let Err(err) = create_csv_in(matrix_in,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-    if let Err(err) = create_csv_ext(matrix_ext,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-            recorders::record(&sim_state, &params, &mut recording_state);
+    files.extend(write_snapshot(&sim_state.ts, &params, sim_state.timestep, &snapshots, output));
         
         }
         
@@ -294,21 +368,8 @@ fn coord_main(params_file_path: &str
         if var > 0.0{
         if var <= f64::pow(10.0,-10) { 
         // Não sei de onde tirar esse número
-        let matrix_out = graph::graph_to_points(&sim_state.ts.layers[0]);
-        let matrix_in = graph::graph_to_points(&sim_state.ts.layers[1]);
-        let matrix_ext = graph::graph_to_points(&convex_hull_from_graph(&sim_state.ts.layers[0]));
-
-    // Chamar a função para criar o arquivo CSV
-    if let Err(err) = create_csv_out(matrix_out,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }	
-    if let Err(err) = create_csv_in(matrix_in,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-    if let Err(err) = create_csv_ext(matrix_ext,output) {
-        eprintln!("Erro ao criar o arquivo CSV: {}", err);
-    }			
-            recorders::record(&sim_state, &params, &mut recording_state);
+        // Only the latest files: this goes off on every step once K settles
+        files.extend(write_latest(&surface_to_export(&sim_state.ts, &params), output));
             
         
 
@@ -326,6 +387,171 @@ fn media(x: &f64, t: &f64) -> f64 {
 
 
 
+fn box_counting_main(input_path: &str, output_path: &str) {
+    match analysis::box_counting::analyse_csv(input_path, output_path, &analysis::box_counting::default_epsilons()) {
+        Ok(fit) => println!("D = {:.4}, c = {:.4} (R^2 = {:.4})", fit.dimension, fit.intercept, fit.r_squared),
+        Err(err) => eprintln!("Erro ao calcular a dimensão fractal: {}", err),
+    }
+}
+
+// Returns the files written
+fn heat_analysis(ts: &graph::types::ThickSurface, heat_params: &analysis::helmholtz::HeatParams, output: &str) -> Vec<String> {
+    let solution = match analysis::helmholtz::solve(ts, heat_params) {
+        Ok(solution) => solution,
+        Err(err) => {
+            eprintln!("Erro na análise de calor: {}", err);
+            return vec![];
+        }
+    };
+    let mut files = analysis::helmholtz::write_slices(&solution, output).unwrap_or_else(|err| {
+        eprintln!("Erro ao criar o arquivo CSV: {}", err);
+        vec![]
+    });
+    let summary_path = format!("{}/heat_summary.csv", output);
+    match analysis::helmholtz::write_summary(&solution, &summary_path) {
+        Ok(()) => files.push(summary_path),
+        Err(err) => eprintln!("Erro ao criar o arquivo CSV: {}", err),
+    }
+    println!("Tempo até o estado estacionário: {:?}", solution.time_to_steady_state());
+    files
+}
+
+// Runs the heat analysis on a finished run or a .wkt/.geojson surface (see file_io::read_surface), writing the results next to it
+fn helmholtz_main(params_file_path: &str, path: &str) {
+    let params_table: toml::Value = match std::fs::read_to_string(params_file_path) {
+        Err(_) => panic!("Parameter file named \"{}\" not found.", params_file_path),
+        Ok(content) => content.parse::<toml::Value>().unwrap(),
+    };
+    let heat_params = file_io::toml_table_to_params(params_table).heat.unwrap_or_default();
+    match file_io::read_surface(path) {
+        Ok((ts, folder)) => {
+            heat_analysis(&ts, &heat_params, &folder);
+        }
+        Err(err) => eprintln!("Erro ao ler a superfície: {}", err),
+    }
+}
+
+fn write_figure(ts: &graph::types::ThickSurface, stitching: Option<&stitcher::types::Stitching>, svg_params: &file_io::svg::SvgParams, file_path: &str) -> Option<String> {
+    match file_io::svg::write_svg_of(ts, stitching, svg_params, file_path) {
+        Ok(()) => Some(String::from(file_path)),
+        Err(err) => {
+            eprintln!("Erro ao criar o SVG: {}", err);
+            None
+        }
+    }
+}
+
+// Draws a finished run or a .wkt/.geojson surface into surface.svg next to it, styled by the [svg] table
+fn svg_main(params_file_path: &str, path: &str) {
+    let params_table: toml::Value = match std::fs::read_to_string(params_file_path) {
+        Err(_) => panic!("Parameter file named \"{}\" not found.", params_file_path),
+        Ok(content) => content.parse::<toml::Value>().unwrap(),
+    };
+    let svg_params = file_io::toml_table_to_params(params_table).svg.unwrap_or_default();
+    match file_io::read_surface(path) {
+        // The stitching isn't saved with a run
+        Ok((ts, folder)) => {
+            write_figure(&ts, None, &svg_params, &format!("{}/surface.svg", folder));
+        }
+        Err(err) => eprintln!("Erro ao ler a superfície: {}", err),
+    }
+}
+
+// Rasterizes a finished run or a .wkt/.geojson surface into labels.pgm, labels.png and labels.csv next to it
+fn raster_main(path: &str, width: usize, height: usize) {
+    let (ts, folder) = match file_io::read_surface(path) {
+        Ok(read) => read,
+        Err(err) => return eprintln!("Erro ao ler a superfície: {}", err),
+    };
+    let grid = analysis::raster::rasterize(
+        &ts,
+        &analysis::raster::RasterParams {
+            width,
+            height,
+            ..Default::default()
+        },
+    );
+    for result in [
+        grid.write_pgm(&format!("{}/labels.pgm", folder)),
+        grid.write_png(&format!("{}/labels.png", folder)),
+        grid.write_csv(&format!("{}/labels.csv", folder)),
+    ] {
+        if let Err(err) = result {
+            eprintln!("Erro ao salvar a grade: {}", err);
+        }
+    }
+}
+
+// A finished run or a .wkt/.geojson surface as surface.wkt and surface.geojson next to it
+fn gis_main(path: &str) {
+    let (ts, folder) = match file_io::read_surface(path) {
+        Ok(read) => read,
+        Err(err) => return eprintln!("Erro ao ler a superfície: {}", err),
+    };
+    for result in [
+        file_io::gis::write_wkt(&ts, &format!("{}/surface.wkt", folder)),
+        file_io::gis::write_geojson(&ts, &format!("{}/surface.geojson", folder)),
+    ] {
+        if let Err(err) = result {
+            eprintln!("Erro ao salvar a superfície: {}", err);
+        }
+    }
+}
+
+// The surface at some timestep of a trajectory.bin, as dados_out.csv, dados_in.csv and dados_ext.csv in folder
+fn trajectory_main(file_path: &str, timestep: u64, folder: &str) {
+    let ts = match file_io::trajectory::Trajectory::open(file_path) {
+        Ok(t) => t.surface_at(timestep),
+        Err(err) => return eprintln!("Erro ao ler a trajetória: {}", err),
+    };
+    match ts {
+        Ok(ts) => {
+            write_latest(&ts, folder);
+        }
+        Err(err) => eprintln!("Erro ao ler a trajetória: {}", err),
+    }
+}
+
+// Every name `recorders` takes, with its units, cost and what it records
+fn list_recorders_main() {
+    let width = recorders::REGISTRY.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    for r in recorders::REGISTRY {
+        println!("{:w$}  {:9}  {} [{}]", r.name, r.cost.name(), r.description, r.units, w = width);
+    }
+}
+
+// Opens my_gui on a finished run: a trajectory.bin, a snapshots.csv or a folder of snapshot_*.csv files, optionally with
+// the recorders CSV the run wrote
+fn replay_main(path: &str, recorders_path: Option<&str>) {
+    let source = match file_io::replay::ReplaySource::open(path) {
+        Ok(s) => s,
+        Err(err) => return eprintln!("Erro ao ler a execução: {}", err),
+    };
+    let log = match recorders_path.map(file_io::replay::RecorderLog::read) {
+        Some(Err(err)) => return eprintln!("Erro ao ler o arquivo CSV: {}", err),
+        Some(Ok(log)) => Some(log),
+        None => None,
+    };
+    my_gui::replay_main(source, log);
+}
+
+// Thickness histogram of a finished run or a .wkt/.geojson surface, written to thickness_hist.csv next to it
+fn thickness_main(path: &str, num_bins: usize) {
+    if num_bins == 0 {
+        return eprintln!("O histograma precisa de pelo menos um intervalo");
+    }
+    let (ts, folder) = match file_io::read_surface(path) {
+        Ok(read) => read,
+        Err(err) => return eprintln!("Erro ao ler a superfície: {}", err),
+    };
+    let thicknesses = analysis::thickness::from_nearest_points(&ts);
+    let s = analysis::thickness::stats(&thicknesses);
+    println!("mean = {}, sd = {}, min = {}, max = {}", s.mean, s.sd, s.min, s.max);
+    if let Err(err) = analysis::thickness::write_histogram_csv(&thicknesses, num_bins, &format!("{}/thickness_hist.csv", folder)) {
+        eprintln!("Erro ao criar o arquivo CSV: {}", err);
+    }
+}
+
 fn playin_main() {
     let (mut renderer, mut window) = renderer::setup_renderer();
     draw_mode_rendering(&mut window, &mut renderer)
@@ -343,11 +569,29 @@ fn main() {
         println!("Path hehe: djumba");
     } else if args[1] == "conrod" {
         shared_shit::conrod_main();
+    } else if args[1] == "my_gui" && args.len() > 3 && args[2] == "replay" {
+        replay_main(&args[3], args.get(4).map(|s| s.as_str()));
     } else if args[1] == "my_gui" {
         my_gui::my_ui_main();
     } else if args[1] == "no_gui" {
         no_gui_main(&args[2], args[3].parse::<u64>().unwrap());
     } else if args[1] == "coord" {
         coord_main(&args[2], args[3].parse::<u64>().unwrap(),&args[4]);
+    } else if args[1] == "box_counting" {
+        box_counting_main(&args[2], &args[3]);
+    } else if args[1] == "helmholtz" {
+        helmholtz_main(&args[2], &args[3]);
+    } else if args[1] == "thickness" {
+        thickness_main(&args[2], args[3].parse::<usize>().unwrap());
+    } else if args[1] == "trajectory" {
+        trajectory_main(&args[2], args[3].parse::<u64>().unwrap(), &args[4]);
+    } else if args[1] == "list-recorders" {
+        list_recorders_main();
+    } else if args[1] == "gis" {
+        gis_main(&args[2]);
+    } else if args[1] == "svg" {
+        svg_main(&args[2], &args[3]);
+    } else if args[1] == "raster" {
+        raster_main(&args[2], args[3].parse::<usize>().unwrap(), args[4].parse::<usize>().unwrap());
     }
 }
@@ -4,8 +4,10 @@ use conrod_core::*;
 
 
 
+use graph::history::History;
 use graph::types::{INNER, OUTER};
-use linalg_helpers::{lines_intersection, points_to_cyclic_lines, closest_point};
+use graph::spatial::SegmentGrid;
+use linalg_helpers::closest_point;
 use my_gui::run_mode::counter_logic;
 
 
@@ -17,42 +19,59 @@ use simulated_annealing::SimState;
 pub struct DrawMode {
     pub(crate) drawing_layers: Vec<Vec<(f64, f64)>>,
     pub(crate) attempted_intersection: usize,
-    pub(crate) layer_id: usize
+    pub(crate) layer_id: usize,
+    pub(crate) history: History<Vec<Vec<(f64, f64)>>>
 }
 
+const HISTORY_LIMIT: usize = 100;
+
 impl DrawMode {
     pub fn new() -> Self {
         DrawMode {
             drawing_layers: vec![Vec::new(), Vec::new()],
             attempted_intersection: 0,
-            layer_id: OUTER
+            layer_id: OUTER,
+            history: History::new(HISTORY_LIMIT)
         }
     }
     pub fn from_inherit(ss: SimState) -> Self {
         DrawMode {
             drawing_layers: ss.ts.layers.iter().map(| g | g.to_vec_of_points()).collect(),
             attempted_intersection: 0,
-            layer_id: OUTER
+            layer_id: OUTER,
+            history: History::new(HISTORY_LIMIT)
         }
     }
 }
 
-pub fn handle_app_state(app: &mut DrawMode, mouse_pos: &[f64; 2], just_pressed_left: bool, just_pressed_right: bool) {
+pub fn handle_app_state(app: &mut DrawMode, mouse_pos: &[f64; 2], just_pressed_left: bool, just_pressed_right: bool, just_pressed_undo: bool, just_pressed_redo: bool) {
     const NUM_ITERATIONS_TIL_THING_DISAPPEARS: usize = 450;
     let layer_id = app.layer_id;
-    let lines = points_to_cyclic_lines(&app.drawing_layers);
     // Left tries adding
     if just_pressed_left {
-        match lines_intersection(&lines) {
-            Some(_) => panic!("Can't add node here, would intersect at _"),
-            None => app.drawing_layers[layer_id].push((mouse_pos[0], mouse_pos[1]))
+        let mut with_point = app.drawing_layers.clone();
+        with_point[layer_id].push((mouse_pos[0], mouse_pos[1]));
+        match SegmentGrid::from_points(&with_point, 0.0).crossings().next() {
+            Some((_, _, at)) => panic!("Can't add node here, would intersect at {:?}", at),
+            None => {
+                app.history.record(app.drawing_layers.clone());
+                app.drawing_layers[layer_id].push((mouse_pos[0], mouse_pos[1]))
+            }
         }
     }
     // Right deletes last added
     if just_pressed_right {
         let l = app.drawing_layers[layer_id].len();
+        app.history.record(app.drawing_layers.clone());
         app.drawing_layers[layer_id].truncate(l - 1)
     }
+    // With nothing to undo or redo the keys do nothing
+    if just_pressed_undo && app.history.can_undo() {
+        app.drawing_layers = app.history.undo(app.drawing_layers.clone());
+    }
+    if just_pressed_redo && app.history.can_redo() {
+        app.drawing_layers = app.history.redo(app.drawing_layers.clone());
+    }
     counter_logic(&mut app.attempted_intersection, NUM_ITERATIONS_TIL_THING_DISAPPEARS);
 }
 
@@ -103,7 +122,9 @@ pub fn gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut DrawMode, mouse_po
         .w_h(button_width, button_height)
         .set(ids.button, ui)
     {
-        *app = DrawMode::new()
+        // Undoable, unlike starting over with DrawMode::new()
+        app.history.record(app.drawing_layers.clone());
+        app.drawing_layers = vec![Vec::new(), Vec::new()];
     }
 
     // TODO: How to exit?
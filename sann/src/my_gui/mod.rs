@@ -1,5 +1,6 @@
 mod run_mode;
 mod draw_mode;
+mod replay_mode;
 
 use conrod_core::*;
 use conrod_piston::event::GenericEvent;
@@ -9,6 +10,9 @@ use piston_window::OpenGL;
 use piston_window::{G2d, G2dTexture, TextureSettings};
 use piston_window::{PistonWindow, Window, WindowSettings};
 use my_gui::draw_mode::DrawMode;
+use my_gui::replay_mode::ReplayMode;
+use file_io::replay::{RecorderLog, ReplaySource};
+use file_io::recorders::REGISTRY;
 
 pub const WIN_W: u32 = 1600;
 pub const WIN_H: u32 = 840;
@@ -38,21 +42,26 @@ pub fn theme() -> conrod_core::Theme {
 
 enum GuiMode {
     Run(RunModeAppState),
-    Draw(DrawMode)
+    Draw(DrawMode),
+    Replay(ReplayMode),
 }
 struct App {
     mode: GuiMode,
     mouse_pos: [f64; 2],
     just_pressed_left: bool,
-    just_pressed_right: bool
+    just_pressed_right: bool,
+    just_pressed_undo: bool,
+    just_pressed_redo: bool
 }
 impl App {
-    fn new() -> App {
+    fn new(mode: GuiMode) -> App {
         App {
-            mode: GuiMode::Run(RunModeAppState::new()),
+            mode: mode,
             mouse_pos: [0.0, 0.0],
             just_pressed_left: false,
             just_pressed_right: false,
+            just_pressed_undo: false,
+            just_pressed_redo: false,
         }
     }
     fn handle_event(&mut self, e: &event::Input) {
@@ -64,9 +73,14 @@ impl App {
             event::Input::Motion(input::Motion::MouseCursor { x, y }) => self.mouse_pos = [*x, *y],
             event::Input::Release(input::Button::Mouse(input::MouseButton::Left)) => self.just_pressed_left = true,
             event::Input::Release(input::Button::Mouse(input::MouseButton::Right)) => self.just_pressed_right = true,
+            // Z undoes, Y redoes
+            event::Input::Release(input::Button::Keyboard(input::Key::Z)) => self.just_pressed_undo = true,
+            event::Input::Release(input::Button::Keyboard(input::Key::Y)) => self.just_pressed_redo = true,
             _ => {
                 self.just_pressed_left = false;
-                self.just_pressed_right = false
+                self.just_pressed_right = false;
+                self.just_pressed_undo = false;
+                self.just_pressed_redo = false
             }
         }
     }
@@ -89,15 +103,22 @@ where
     }
 }
 
-fn attach_gui_instance_to_ui<T>(event: &T, ui: &mut Ui, app: &mut App, run_widget_ids: &run_mode::Ids, draw_widget_ids: &draw_mode::Ids)
-where
+fn attach_gui_instance_to_ui<T>(
+    event: &T,
+    ui: &mut Ui,
+    app: &mut App,
+    run_widget_ids: &run_mode::Ids,
+    draw_widget_ids: &draw_mode::Ids,
+    replay_widget_ids: &mut replay_mode::Ids,
+) where
     T: GenericEvent + Clone,
 {
     event.update(|_| {
         let mut ui = ui.set_widgets();
         match &mut app.mode {
             GuiMode::Run(r) => run_mode::gui(&mut ui, run_widget_ids, r),
-            GuiMode::Draw(d) => draw_mode::gui(&mut ui, draw_widget_ids, d, app.mouse_pos)
+            GuiMode::Draw(d) => draw_mode::gui(&mut ui, draw_widget_ids, d, app.mouse_pos),
+            GuiMode::Replay(r) => replay_mode::gui(&mut ui, replay_widget_ids, r),
         }
     });
 }
@@ -111,7 +132,14 @@ fn handle_app_state(app: &mut App) {
             }
         }
         GuiMode::Draw(d) => {
-            draw_mode::handle_app_state(d, &app.mouse_pos, app.just_pressed_left, app.just_pressed_right);
+            draw_mode::handle_app_state(
+                d,
+                &app.mouse_pos,
+                app.just_pressed_left,
+                app.just_pressed_right,
+                app.just_pressed_undo,
+                app.just_pressed_redo,
+            );
             // We need to de-set these variables here becuase this fn gets called MORE than the input handler
             if app.just_pressed_left {
                 app.just_pressed_left = false
@@ -119,12 +147,31 @@ fn handle_app_state(app: &mut App) {
             if app.just_pressed_right {
                 app.just_pressed_right = false
             };
+            if app.just_pressed_undo {
+                app.just_pressed_undo = false
+            };
+            if app.just_pressed_redo {
+                app.just_pressed_redo = false
+            };
             // TODO: How to come back?
         }
+        GuiMode::Replay(r) => replay_mode::handle_app_state(r),
     }
 }
 
 pub fn my_ui_main() {
+    run(App::new(GuiMode::Run(RunModeAppState::new())));
+}
+
+// Plays back a finished run instead of starting a new one. Without a log, no recorder values are shown
+pub fn replay_main(source: ReplaySource, log: Option<RecorderLog>) {
+    match ReplayMode::new(source, log) {
+        Ok(r) => run(App::new(GuiMode::Replay(r))),
+        Err(e) => eprintln!("Can't replay: {}", e),
+    }
+}
+
+fn run(mut app: App) {
     const WIDTH: u32 = WIN_W;
     const HEIGHT: u32 = WIN_H;
 
@@ -168,11 +215,12 @@ pub fn my_ui_main() {
     let image_map = conrod_core::image::Map::new();
 
     // Instantiate the generated list of widget identifiers.
-    let run_mode_ids = run_mode::Ids::new(ui.widget_id_generator());
+    let mut run_mode_ids = run_mode::Ids::new(ui.widget_id_generator());
+    run_mode_ids.recorder_toggles.resize(REGISTRY.len(), &mut ui.widget_id_generator());
     let draw_mode_ids = draw_mode::Ids::new(ui.widget_id_generator());
+    // Mutable because its lists of ids grow with the surface
+    let mut replay_mode_ids = replay_mode::Ids::new(ui.widget_id_generator());
 
-    // A demonstration of some state that we'd like to control with the App.
-    let mut app = App::new();
     // Poll events from the window.
     while let Some(event) = window.next() {
         // Step 1: Handle app (not gui) state. Meaning stateful changes that happen to the application but don't necessarily result in a visible difference
@@ -182,7 +230,7 @@ pub fn my_ui_main() {
         handle_input_event(&window, &event, &mut ui, &mut app);
 
         // Step 3: Mutate the ui with a new instance of the gui
-        attach_gui_instance_to_ui(&event, &mut ui, &mut app, &run_mode_ids, &draw_mode_ids);
+        attach_gui_instance_to_ui(&event, &mut ui, &mut app, &run_mode_ids, &draw_mode_ids, &mut replay_mode_ids);
 
         // Step 4: Draw the collected primitives to the screen
         window.draw_2d(&event, |context, graphics, device| {
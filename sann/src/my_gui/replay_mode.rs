@@ -0,0 +1,205 @@
+use conrod_core::*;
+
+use file_io::replay::{RecorderLog, ReplaySource};
+use graph::types::ThickSurface;
+use renderer::lines_from_thick_surface;
+
+/// Playback of a finished run: one recorded timestep at a time, forwards on its own or wherever the scrubber puts it.
+pub struct ReplayMode {
+    source: ReplaySource,
+    timesteps: Vec<u64>,
+    // Index into timesteps
+    position: usize,
+    ts: ThickSurface,
+    log: Option<RecorderLog>,
+    is_paused: bool,
+}
+
+impl ReplayMode {
+    pub fn new(source: ReplaySource, log: Option<RecorderLog>) -> Result<Self, String> {
+        let timesteps = source.timesteps();
+        let ts = match timesteps.first() {
+            Some(t) => source.surface_at(*t)?,
+            None => return Err(String::from("nothing to replay")),
+        };
+        Ok(ReplayMode {
+            source: source,
+            timesteps: timesteps,
+            position: 0,
+            ts: ts,
+            log: log,
+            is_paused: true,
+        })
+    }
+
+    fn timestep(&self) -> u64 {
+        self.timesteps[self.position]
+    }
+
+    fn seek(&mut self, position: usize) {
+        let position = position.min(self.timesteps.len() - 1);
+        if position == self.position {
+            return;
+        }
+        match self.source.surface_at(self.timesteps[position]) {
+            Ok(ts) => {
+                self.ts = ts;
+                self.position = position;
+            }
+            Err(e) => {
+                eprintln!("Couldn't rebuild timestep {}: {}", self.timesteps[position], e);
+                self.is_paused = true;
+            }
+        }
+    }
+}
+
+pub fn handle_app_state(app: &mut ReplayMode) {
+    if !app.is_paused {
+        if app.position + 1 < app.timesteps.len() {
+            let next = app.position + 1;
+            app.seek(next);
+        } else {
+            // Stops on the last timestep instead of looping
+            app.is_paused = true;
+        }
+    }
+}
+
+// Generate a unique `WidgetId` for each widget.
+widget_ids! {
+    pub struct Ids {
+        // The scrollable canvas.
+        canvas,
+        // Silly anchor
+        anchor,
+        // Playback controls
+        toggle,
+        step_back,
+        step_forward,
+        scrubber,
+        timestep,
+        // Recorders
+        title_recorders,
+        recorder_values[],
+        // One per line of lines_from_thick_surface
+        lines[],
+    }
+}
+
+pub fn gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut ReplayMode) {
+    const MARGIN: conrod_core::Scalar = 30.0;
+    const INPUT_FT_SIZE: conrod_core::FontSize = 13;
+    // Where the surface's origin goes, right of the controls
+    const SURFACE_X: conrod_core::Scalar = 250.0;
+
+    // `Canvas` is a widget that provides some basic functionality for laying out children widgets.
+    // By default, its size is the size of the window. We'll use this as a background for the
+    // following widgets, as well as a scrollable container for the children widgets.
+    widget::Canvas::new().pad(MARGIN).scroll_kids_vertically().set(ids.canvas, ui);
+
+    // Empty text box serves as anchor
+    widget::text::Text::new("")
+        .top_left_with_margin_on(ids.canvas, MARGIN - 20.0)
+        .set(ids.anchor, ui);
+
+    /////////////////////////////////
+    ////// Playback controls ////////
+    /////////////////////////////////
+    let button_width = ui.kid_area_of(ids.canvas).unwrap().w() * 0.12;
+    let button_height = ui.kid_area_of(ids.canvas).unwrap().h() * 0.05;
+    let label = if app.is_paused { "Play" } else { "Pause" };
+    for _ in widget::Toggle::new(app.is_paused)
+        .label(label)
+        .label_color(if app.is_paused {
+            conrod_core::color::WHITE
+        } else {
+            conrod_core::color::LIGHT_CHARCOAL
+        })
+        .down_from(ids.anchor, 20.0)
+        .w_h(button_width, button_height)
+        .set(ids.toggle, ui)
+    {
+        // Playing from the end starts over
+        if app.is_paused && app.position + 1 == app.timesteps.len() {
+            app.seek(0);
+        }
+        app.is_paused = !app.is_paused;
+    }
+
+    for _press in widget::Button::new()
+        .label("Step back")
+        .down_from(ids.toggle, 20.0)
+        .w_h(button_width, button_height)
+        .set(ids.step_back, ui)
+    {
+        app.is_paused = true;
+        let previous = app.position.saturating_sub(1);
+        app.seek(previous);
+    }
+    for _press in widget::Button::new()
+        .label("Step forward")
+        .right_from(ids.step_back, 20.0)
+        .w_h(button_width, button_height)
+        .set(ids.step_forward, ui)
+    {
+        app.is_paused = true;
+        let next = app.position + 1;
+        app.seek(next);
+    }
+
+    // The scrubber goes over record indices, not timesteps, so every position has a surface
+    let last = app.timesteps.len() - 1;
+    if let Some(v) = widget::Slider::new(app.position as f32, 0.0, last.max(1) as f32)
+        .label("timeline")
+        .label_color(conrod_core::color::WHITE)
+        .down_from(ids.step_back, 20.0)
+        .w_h(button_width * 2.0 + 20.0, button_height * 0.6)
+        .set(ids.scrubber, ui)
+    {
+        app.is_paused = true;
+        app.seek(v.round() as usize);
+    }
+
+    let timestep_text = format!("timestep {} ({} of {})", app.timestep(), app.position + 1, app.timesteps.len());
+    widget::Text::new(&timestep_text)
+        .down_from(ids.scrubber, 20.0)
+        .font_size(INPUT_FT_SIZE)
+        .set(ids.timestep, ui);
+
+    /////////////////////////////////
+    ////// Recorder values //////////
+    /////////////////////////////////
+    widget::Text::new("Recorders")
+        .down_from(ids.timestep, 20.0)
+        .set(ids.title_recorders, ui);
+    let recorder_lines: Vec<String> = match &app.log {
+        Some(log) => match log.values_at(app.timestep()) {
+            Some(values) => log.names.iter().zip(values.iter()).map(|(n, v)| format!("{}: {}", n, v)).collect(),
+            None => vec![String::from("nothing recorded yet")],
+        },
+        None => vec![String::from("no recorder file given")],
+    };
+    ids.recorder_values.resize(recorder_lines.len(), &mut ui.widget_id_generator());
+    let mut p = ids.title_recorders;
+    for (text, id) in recorder_lines.iter().zip(ids.recorder_values.iter()) {
+        widget::Text::new(text)
+            .down_from(p, 10.0)
+            .font_size(INPUT_FT_SIZE)
+            .set(*id, ui);
+        p = *id;
+    }
+
+    /////////////////////////////////
+    //// Actual line rendering //////
+    /////////////////////////////////
+    let lines = lines_from_thick_surface(&app.ts);
+    ids.lines.resize(lines.len(), &mut ui.widget_id_generator());
+    for (l, id) in lines.iter().zip(ids.lines.iter()) {
+        let (x1, y1, x2, y2) = l.points;
+        let [r, g, b, a] = l.color;
+        widget::Line::abs([x1 * 400.0 + SURFACE_X, y1 * 400.0], [x2 * 400.0 + SURFACE_X, y2 * 400.0])
+            .color(Color::Rgba(r, g, b, a))
+            .set(*id, ui);
+    }
+}
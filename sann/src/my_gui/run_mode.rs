@@ -2,7 +2,7 @@ use conrod_core::*;
 
 use conrod_core::widget::text_box::Event;
 use conrod_core::widget::Id;
-use file_io::recorders::{rec_map, record, RecordingState};
+use file_io::recorders::{record, Cadence, RecordingState, REGISTRY};
 use file_io::toml_table_to_params;
 use graph::types::{INNER, OUTER};
 use num_traits::NumCast;
@@ -69,10 +69,7 @@ impl RunModeAppState {
             Err(_) => panic!("No parameters.toml file found in directory"),
             Ok(content) => toml_table_to_params(content.parse::<toml::Value>().unwrap()),
         };
-        let mut r = HashMap::new();
-        for (rn, _fn_) in rec_map() {
-            r.insert(rn, false);
-        }
+        let r = no_recorders_selected();
         RunModeAppState {
             sim: SimState::initial_state(&params),
             is_paused: true,
@@ -87,10 +84,7 @@ impl RunModeAppState {
         }
     }
     pub fn from(ss: SimState, params: Params) -> Self {
-        let mut r = HashMap::new();
-        for (rn, _fn_) in rec_map() {
-            r.insert(rn, false);
-        }
+        let r = no_recorders_selected();
         RunModeAppState {
             sim: ss,
             is_paused: true,
@@ -106,6 +100,10 @@ impl RunModeAppState {
     }
 }
 
+fn no_recorders_selected() -> HashMap<String, bool> {
+    REGISTRY.iter().map(|r| (String::from(r.name), false)).collect()
+}
+
 pub fn counter_logic(lil_counter: &mut usize, lim: usize) {
     if *lil_counter > 0 {
         *lil_counter = *lil_counter + 1;
@@ -217,14 +215,8 @@ widget_ids! {
         blue_convex,
         // Recorders
         title_recorders,
-        energy,
-        outer_perimeter,
-        inner_perimeter,
-        outer_area,
-        inner_area,
-        gray_matter_area,
-        num_inner_points,
-        num_outer_points,
+        // One per entry of the recorder registry, sized when the Ids are made
+        recorder_toggles[],
         // File navigator for deciding output
         file_nav,
         // Scrollbar
@@ -292,24 +284,14 @@ fn make_recorder_widgets(anchor_id: Id, ids: &Ids, app: &mut RunModeAppState, ui
     /////////////////////////////////
     const INPUT_FT_SIZE: conrod_core::FontSize = 13;
 
-    let rec_ids: HashMap<String, Id> = vec![
-        (String::from("energy"), ids.energy),
-        (String::from("outer perimeter"), ids.outer_perimeter),
-        (String::from("inner perimeter"), ids.inner_perimeter),
-        (String::from("outer area"), ids.outer_area),
-        (String::from("inner area"), ids.inner_area),
-        (String::from("gray matter area"), ids.gray_matter_area),
-        (String::from("num inner points"), ids.num_inner_points),
-        (String::from("num outer points"), ids.num_outer_points),
-    ]
-    .into_iter()
-    .collect();
+    // Every recorder of the registry, in its order
     let mut p = anchor_id;
     let mut new_recorders_selection_map = app.recorders_selection_map.clone();
-    for (rn, activated) in &app.recorders_selection_map {
-        let n = rec_ids.get(rn).unwrap();
+    for (r, n) in REGISTRY.iter().zip(ids.recorder_toggles.iter()) {
+        let rn = String::from(r.name);
+        let activated = app.recorders_selection_map.get(&rn).unwrap_or(&false);
         for e in widget::Toggle::new(*activated)
-            .label(rn)
+            .label(r.name)
             .label_x(conrod_core::position::Relative::Scalar(80.0))
             .color(if *activated {
                 conrod_core::color::GREEN
@@ -493,12 +475,12 @@ pub fn gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut RunModeAppState) {
         .w_h(button_width, button_height)
         .set(ids.button, ui)
     {
-        app.params.recorders = app
-            .recorders_selection_map
+        app.params.recorders = REGISTRY
             .iter()
-            .filter_map(|(k, v)| if *v { Some(k.clone()) } else { None })
+            .filter(|r| app.recorders_selection_map.get(r.name) == Some(&true))
+            .map(|r| String::from(r.name))
             .collect();
-        app.recording_state = RecordingState::initial_state(&app.params).unwrap();
+        app.recording_state = RecordingState::initial_state(&app.params, &Cadence::every(1)).unwrap();
         app.sim = SimState::initial_state(&app.params);
         app.is_paused = true;
     }
@@ -1,4 +1,5 @@
 use glutin_window::GlutinWindow as Window;
+use graph::history::History;
 use graph::types::{NodeChange, NodeChangeMap, Smooth, ThickSurface, INNER, OUTER};
 use graph::{
     closest_node_to_some_point, cyclic_graph_from_coords, distance_between_points,
@@ -222,6 +223,26 @@ fn state_effects(s: &State, e: Event, last_mouse_pos: (f64, f64)) -> State {
             _ => s.clone(),
         },
         State::SurfaceUnstitched(ts) => match e.press_args() {
+            Some(Button::Keyboard(piston::Key::A)) => {
+                let mut new_ts = ts.clone();
+                match new_ts.best_effort_add(last_mouse_pos.0, last_mouse_pos.1) {
+                    Ok(()) => State::SurfaceUnstitched(new_ts),
+                    Err(()) => {
+                        println!("Can't add a node at {:?}, it would cross a layer", last_mouse_pos);
+                        s.clone()
+                    }
+                }
+            }
+            Some(Button::Keyboard(piston::Key::D)) => {
+                let mut new_ts = ts.clone();
+                match new_ts.best_effort_delete(last_mouse_pos.0, last_mouse_pos.1) {
+                    Ok(()) => State::SurfaceUnstitched(new_ts),
+                    Err(()) => {
+                        println!("Can't delete the node at {:?}, it would cross a layer", last_mouse_pos);
+                        s.clone()
+                    }
+                }
+            }
             Some(Button::Keyboard(piston::Key::S)) => {
                 let stitch = stitcher::stitch_choice(&ts, STRAT);
                 State::SurfaceStitched(ts.clone(), stitch)
@@ -259,7 +280,11 @@ fn state_effects(s: &State, e: Event, last_mouse_pos: (f64, f64)) -> State {
                 let next_layer_id = if *last_layer_id == OUTER { INNER } else { OUTER };
                 let next_node = closest_node_to_some_point(&ts.layers[next_layer_id], last_mouse_pos.0, last_mouse_pos.1);
                 let out = (
-                    if *last_layer_id == OUTER { *last_node_id } else { next_node.id },
+                    if *last_layer_id == OUTER {
+                        ts.layers[*last_layer_id].nodes[*last_node_id].handle
+                    } else {
+                        next_node.handle
+                    },
                     if *last_layer_id == OUTER {
                         ts.layers[*last_layer_id].nodes[*last_node_id].x
                     } else {
@@ -272,7 +297,11 @@ fn state_effects(s: &State, e: Event, last_mouse_pos: (f64, f64)) -> State {
                     },
                 );
                 let inn = (
-                    if *last_layer_id == INNER { *last_node_id } else { next_node.id },
+                    if *last_layer_id == INNER {
+                        ts.layers[*last_layer_id].nodes[*last_node_id].handle
+                    } else {
+                        next_node.handle
+                    },
                     if *last_layer_id == INNER {
                         ts.layers[*last_layer_id].nodes[*last_node_id].x
                     } else {
@@ -332,10 +361,30 @@ fn state_effects(s: &State, e: Event, last_mouse_pos: (f64, f64)) -> State {
     }
 }
 
+fn layer_sizes(ts: &ThickSurface) -> Vec<usize> {
+    ts.layers.iter().map(|g| g.nodes.len()).collect()
+}
+
+/* Whether going from old to new was an edit worth undoing: drawing points, turning the drawing into a surface,
+   adding/deleting nodes and stitching by hand. Failed edits leave the state as it was, so they don't count */
+fn is_edit(old: &State, new: &State) -> bool {
+    match (old, new) {
+        (State::Draw(o1, i1), State::Draw(o2, i2)) => o1.len() != o2.len() || i1.len() != i2.len(),
+        (State::Draw(_, _), State::SurfaceUnstitched(_)) | (State::Draw(_, _), State::SurfacePushing(_, _, _)) => true,
+        (State::SurfaceUnstitched(ts1), State::SurfaceUnstitched(ts2)) => layer_sizes(ts1) != layer_sizes(ts2),
+        (State::SurfaceStitchingB(_, s1, _), State::SurfaceStitchingA(_, s2)) => s1.len() != s2.len(),
+        _ => false,
+    }
+}
+
+const HISTORY_LIMIT: usize = 100;
+
+// Z undoes and Y redoes, in any state
 pub fn draw_mode_rendering(window: &mut Window, renderer: &mut Renderer) {
     let mut last_mouse_pos = (0.0, 0.0);
     let mut events = Events::new(EventSettings::new());
     let mut state = State::Draw(Vec::new(), Vec::new());
+    let mut history = History::new(HISTORY_LIMIT);
     while let Some(e) = events.next(window) {
         let lines = state_to_lines(&state, last_mouse_pos);
         if let Some(args) = e.render_args() {
@@ -347,6 +396,18 @@ pub fn draw_mode_rendering(window: &mut Window, renderer: &mut Renderer) {
             None => last_mouse_pos,
         };
 
-        state = state_effects(&state, e, last_mouse_pos);
+        state = match e.press_args() {
+            Some(Button::Keyboard(piston::Key::Z)) if history.can_undo() => history.undo(state),
+            Some(Button::Keyboard(piston::Key::Y)) if history.can_redo() => history.redo(state),
+            // With nothing to undo or redo the keys do nothing
+            Some(Button::Keyboard(piston::Key::Z)) | Some(Button::Keyboard(piston::Key::Y)) => state,
+            _ => {
+                let new_state = state_effects(&state, e, last_mouse_pos);
+                if is_edit(&state, &new_state) {
+                    history.record(state);
+                }
+                new_state
+            }
+        };
     }
 }
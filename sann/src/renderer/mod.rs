@@ -22,6 +22,9 @@ use simulated_annealing::SimState;
 
 use types::Params;
 
+// The GUI's run stops here, if the window isn't closed first
+pub const MAX_STEPS: u64 = 10000;
+
 pub fn lines_from_thick_surface(ts: &ThickSurface) -> Vec<types::Line> {
     let mut lines = Vec::new();
     let color_array = [consts::PINK, consts::BLUE, consts::PURPLE];
@@ -187,11 +190,13 @@ pub fn setup_optimization_and_loop<F>(
     renderer: &mut types::Renderer,
     how_to_make_lines: F,
     params: &Params,
-) where
+) -> recorders::RecordingState
+where
     F: Fn(&SimState) -> Vec<types::Line>,
 {
     let mut render_state = initial_render_state();
-    let mut recording_state = recorders::RecordingState::initial_state(&params);
+    let mut recording_state = recorders::RecordingState::initial_state(&params, &recorders::Cadence::every(1))
+        .unwrap_or_else(|err| panic!("Couldn't create the recorders' file \"{}\": {}", params.output_file_path, err));
     let mut events = Events::new(EventSettings::new());
     let mut imaginary_lines = Vec::new();
 
@@ -214,21 +219,19 @@ pub fn setup_optimization_and_loop<F>(
             StepType::Reset => *sim_state = simulated_annealing::SimState::initial_state(params),
             _ => {}
         }
-        match &mut recording_state {
-            Some(f) => recorders::record(&sim_state, params, f),
-            None => {}
-        }
+        recorders::record(&sim_state, params, &mut recording_state);
         // Se vc quer que pare de rodar
-        if sim_state.timestep >= 10000 {
+        if sim_state.timestep >= MAX_STEPS {
             break;
         }
         // Se vc quer que recomece
         let timestamp = sim_state.timestep;
-        if timestamp >= 10000 {
+        if timestamp >= MAX_STEPS {
             *sim_state = SimState::initial_state(params);
         }
         // Se vc quer rodar de um programa EXTERNO, tbm é uma opção
     }
+    recording_state
 }
 
 pub fn setup_renderer() -> (types::Renderer, Window) {
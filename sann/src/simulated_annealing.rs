@@ -1,10 +1,14 @@
 use graph;
 use graph::circular_thick_surface;
-use graph::effects::{add_node_, apply_changes, changer_of_choice, merge_nodes_, random_change, revert_changes, smooth_change_out};
+use graph::effects::{apply_changes, changer_of_choice, random_change, revert_changes, smooth_change_out};
+use graph::pins::pin;
+use graph::refine::{refine_additions, refine_merges};
+use graph::resample::{even_out, resample_thick_surface, Resolution};
+use graph::separation::{self, Separation};
 use graph::types::{NodeChangeMap, Smooth, ThickSurface, INNER, OUTER};
-use linalg_helpers::lines_intersection;
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use graph::validate::{check_after, containment};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use stitcher::stitch_default;
 use stitcher::types::Stitching;
 use types::Params;
@@ -19,7 +23,7 @@ fn neighbor_changes(
     compression_factor: f64,
     stitch: &Stitching,
     low_high: (f64, f64),
-    rng: &mut rand::rngs::ThreadRng,
+    rng: &mut StdRng,
 ) -> (NodeChangeMap, NodeChangeMap) {
     let outer_change = random_change(&ts.layers[layer_to_push], low_high, rng);
     let smoothed_changes = smooth_change_out(&ts.layers[layer_to_push], outer_change.clone(), Smooth::Count(how_smooth));
@@ -72,80 +76,39 @@ fn intersection_effects(
     energy_state: f64,
     energy_neighbor: f64,
     temperature: f64,
-    rng: &mut rand::rngs::ThreadRng,
-) {
-    let lines1 = graph::graphs_to_lines(&ts.layers);
+    clearance: &Option<Separation>,
+    rng: &mut StdRng,
+) -> bool {
     let coin_flip = rng.gen_range(0.0, 1.0);
-    match lines_intersection(&lines1) {
+    match containment(ts).or_else(|| separation::violation(ts, clearance)) {
         Some(_) => {
             revert_changes(&mut ts.layers[OUTER], outer_changes);
             revert_changes(&mut ts.layers[INNER], inner_changes);
 
-            let lines2 = graph::graphs_to_lines(&ts.layers);
-            match lines_intersection(&lines2) {
-                Some(_) => {
-                    println!("Howwwwwwww?? lines1:\n{:?}\nlines2:\n{:?}", lines1, lines2);
-                    panic!("caceta")
-                }
-                None => {}
+            if let Some(v) = containment(ts) {
+                panic!("Reverting the move didn't leave a proper surface: {}", v);
             }
+            false
         }
         None => {
             if probability_to_accept_neighbor_state(energy_state, energy_neighbor, temperature) < coin_flip {
                 revert_changes(&mut ts.layers[OUTER], outer_changes);
                 revert_changes(&mut ts.layers[INNER], inner_changes);
+                false
+            } else {
+                true
             }
         }
     }
 }
 
-fn add_single_node_effects(ts: &mut ThickSurface, layer_to_add: usize, addition_threshold: f64) {
-    let graph_to_which_add = &ts.layers[layer_to_add];
-
-    for n in &graph_to_which_add.nodes {
-        match graph::node_to_add(graph_to_which_add, n, n.next(&graph_to_which_add), addition_threshold) {
-            Some(addition) => {
-                add_node_(ts, layer_to_add, &addition);
-                // println!("addition: {:?}", addition);
-                // println!("prev: {:?}\nnext: {:?}\n", ts.layers[layer_to_add].nodes[addition.n.prev_id], ts.layers[layer_to_add].nodes[addition.n.next_id]);
-                break; // THE BREAK IS WHAT LETS THIS WORK, GODDAMN
-            }
-            None => {}
-        }
-    }
-}
-
-fn aaa(ts: &ThickSurface) {
-    let mut n = &ts.layers[OUTER].nodes[0];
-    // println!("AAA");
-    loop {
-        n = n.next_by(&ts.layers[OUTER], 1);
-        if n == &ts.layers[OUTER].nodes[0] {
-            break;
-        }
-    }
-    // println!("BBB");
-}
-
-fn delete_single_node_effects(ts: &mut ThickSurface, layer_from_which_delete: usize, deletion_threshold: f64, max_merge_steps_away: usize) {
-    let graph_from_which_delete = &ts.layers[layer_from_which_delete];
-    for n in &graph_from_which_delete.nodes {
-        match graph::nodes_to_merge(ts, layer_from_which_delete, n, deletion_threshold, max_merge_steps_away, true) {
-            Some(deletion) => {
-                merge_nodes_(ts, &deletion);
-
-                let lines = graph::graphs_to_lines(&ts.layers);
-                match lines_intersection(&lines) {
-                    Some(_) => println!("How??"),
-                    None => {}
-                }
-
-                break; // THE BREAK IS WHAT LETS THIS WORK, GODDAMN
-            }
-            None => {}
-        }
-    }
-    aaa(ts)
+// What the last step did with its move, for the recorders
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepOutcome {
+    pub accepted: bool,
+    // Both with the separation penalty, if any
+    pub energy_before: f64,
+    pub energy_proposed: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -154,22 +117,63 @@ pub struct SimState {
     pub temperature: f64,
     pub stitching: Stitching,
     pub timestep: u64,
-    pub rng: ThreadRng,
+    // What rng was seeded with: params.seed, or one drawn when that isn't set
+    pub seed: u64,
+    pub rng: StdRng,
+    // None until the first step
+    pub last_step: Option<StepOutcome>,
 }
 
 impl SimState {
     pub fn initial_state(p: &Params) -> SimState {
-        let ts = circular_thick_surface(p.initial_radius, p.initial_thickness, p.initial_num_points);
+        let mut ts = circular_thick_surface(p.initial_radius, p.initial_thickness, p.initial_num_points);
+        pin(&mut ts, &p.pinned);
         let s = stitch_default(&ts);
-        let rng = rand::thread_rng();
+        // Below 2^63, so it fits in a TOML integer and can be given back as the seed parameter
+        let seed = p.seed.unwrap_or_else(|| rand::random::<u64>() >> 1);
 
-        SimState {
+        let ret = SimState {
             ts: ts,
             temperature: p.initial_temperature,
             stitching: s,
             timestep: 0,
-            rng: rng,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            last_step: None,
+        };
+        ret.debug_check(p, "setup");
+        ret
+    }
+
+    fn debug_check(&self, params: &Params, operation: &str) {
+        if params.debug_checks {
+            check_after(operation, self.timestep, &self.ts, &self.stitching);
+        }
+    }
+
+    /* Swaps the surface for an evenly resampled one and returns true. Node ids all change, so the stitching is redone
+       too. Resampling cuts corners, which can make layers that are very close cross each other: when the remeshed
+       surface isn't a proper one this keeps the current surface and returns false */
+    pub fn remesh(&mut self, spacing: Option<f64>) -> bool {
+        let remeshed = match spacing {
+            Some(s) => resample_thick_surface(&self.ts, Resolution::Spacing(s)),
+            None => even_out(&self.ts),
+        };
+        if containment(&remeshed).is_some() {
+            return false;
         }
+        self.ts = remeshed;
+        self.stitching = stitch_default(&self.ts);
+        true
+    }
+}
+
+// Periodic remeshing, if the parameters ask for it. Steppers call this after advancing the timestep
+pub fn remesh_if_due(sim_state: &mut SimState, params: &Params) {
+    let due = params.remesh_every.is_some_and(|every| every > 0 && sim_state.timestep.is_multiple_of(every));
+    // If it can't be done now it'll be tried again next time it's due
+    if due && sim_state.remesh(params.remesh_spacing) {
+        sim_state.debug_check(params, "remesh");
     }
 }
 
@@ -177,8 +181,6 @@ pub fn step(sim_state: &mut SimState, params: &Params) -> Vec<NodeChangeMap> {
     let how_smooth = params.how_smooth;
     let compression_factor = params.compression_factor;
     let low_high = params.low_high;
-    let node_addition_threshold = params.node_addition_threshold;
-    let node_deletion_threshold = params.node_deletion_threshold;
 
     let (outer_changes, inner_changes) = neighbor_changes(
         &sim_state.ts,
@@ -191,27 +193,37 @@ pub fn step(sim_state: &mut SimState, params: &Params) -> Vec<NodeChangeMap> {
         &mut sim_state.rng,
     );
 
-    let energy_state = energy(&sim_state.ts, params.initial_gray_matter_area);
+    let energy_state = energy(&sim_state.ts, params.initial_gray_matter_area) + separation::penalty(&sim_state.ts, &params.separation);
     apply_changes(&mut sim_state.ts.layers[OUTER], &outer_changes);
     apply_changes(&mut sim_state.ts.layers[INNER], &inner_changes);
-    let energy_neighbor = energy(&sim_state.ts, params.initial_gray_matter_area);
+    let energy_neighbor = energy(&sim_state.ts, params.initial_gray_matter_area) + separation::penalty(&sim_state.ts, &params.separation);
 
-    intersection_effects(
+    let accepted = intersection_effects(
         &mut sim_state.ts,
         &outer_changes,
         &inner_changes,
         energy_state,
         energy_neighbor,
         sim_state.temperature,
+        &params.separation,
         &mut sim_state.rng,
     );
-    add_single_node_effects(&mut sim_state.ts, OUTER, node_addition_threshold);
-    add_single_node_effects(&mut sim_state.ts, INNER, node_addition_threshold);
-
-    delete_single_node_effects(&mut sim_state.ts, OUTER, node_deletion_threshold, params.max_merge_steps_away);
-    delete_single_node_effects(&mut sim_state.ts, INNER, node_deletion_threshold, params.max_merge_steps_away);
+    sim_state.last_step = Some(StepOutcome {
+        accepted,
+        energy_before: energy_state,
+        energy_proposed: energy_neighbor,
+    });
+    sim_state.debug_check(params, "move");
+    refine_additions(&mut sim_state.ts, OUTER, params);
+    refine_additions(&mut sim_state.ts, INNER, params);
+    sim_state.debug_check(params, "add");
+
+    refine_merges(&mut sim_state.ts, OUTER, params, true);
+    refine_merges(&mut sim_state.ts, INNER, params, true);
+    sim_state.debug_check(params, "merge");
 
     sim_state.temperature = temperature(sim_state, params.temperature_param);
     sim_state.timestep += 1;
+    remesh_if_due(sim_state, params);
     vec![outer_changes, inner_changes]
 }
@@ -1,9 +1,12 @@
 use graph;
-use graph::effects::{add_node_, apply_changes, changer_of_choice, merge_nodes_, random_change, smooth_change_out};
+use graph::effects::{apply_changes, changer_of_choice, random_change, smooth_change_out};
+use graph::refine::{refine_additions, refine_merges};
+use graph::separation;
 use graph::types::{Smooth, ThickSurface, INNER, OUTER};
-use linalg_helpers::lines_intersection;
+use graph::validate::{containment, report, validate_all, Violation};
+use rand::rngs::StdRng;
 use rand::Rng;
-use simulated_annealing::SimState;
+use simulated_annealing::{remesh_if_due, SimState, StepOutcome};
 use stitcher::types::Stitching;
 use types::Params;
 
@@ -17,11 +20,20 @@ fn neighbor(
     compression_factor: f64,
     stitch: &Stitching,
     low_high: (f64, f64),
-    addition_threshold: f64,
-    deletion_threshold: f64,
-    max_merge_steps_away: usize,
-    rng: &mut rand::rngs::ThreadRng,
-) -> ThickSurface {
+    params: &Params,
+    rng: &mut StdRng,
+) -> (ThickSurface, Option<(&'static str, Vec<Violation>)>) {
+    // With debug_checks, the first operation after which the neighbor is no longer a valid surface
+    let mut broken_by = None;
+    let mut check = |operation: &'static str, ts: &ThickSurface| {
+        if params.debug_checks && broken_by.is_none() {
+            let violations = validate_all(ts, stitch);
+            if !violations.is_empty() {
+                broken_by = Some((operation, violations));
+            }
+        }
+    };
+
     let mut ret = ts.clone();
     let outer_change = random_change(&ret.layers[layer_to_push], low_high, rng);
     let smoothed_changes = smooth_change_out(&ret.layers[layer_to_push], outer_change.clone(), Smooth::Count(how_smooth));
@@ -34,14 +46,17 @@ fn neighbor(
     );
     apply_changes(&mut ret.layers[layer_to_push], &smoothed_changes);
     apply_changes(&mut ret.layers[layer_across], &smoothed_inner_changes);
+    check("move", &ret);
 
-    add_single_node_effects(&mut ret, layer_to_push, addition_threshold);
-    add_single_node_effects(&mut ret, layer_across, addition_threshold);
+    refine_additions(&mut ret, layer_to_push, params);
+    refine_additions(&mut ret, layer_across, params);
+    check("add", &ret);
 
-    delete_single_node_effects(&mut ret, layer_to_push, deletion_threshold, max_merge_steps_away);
-    delete_single_node_effects(&mut ret, layer_across, deletion_threshold, max_merge_steps_away);
+    refine_merges(&mut ret, layer_to_push, params, false);
+    refine_merges(&mut ret, layer_across, params, false);
+    check("merge", &ret);
 
-    ret
+    (ret, broken_by)
 }
 
 pub fn energy(ts: &ThickSurface, initial_gray_matter_area: f64) -> f64 {
@@ -76,10 +91,16 @@ fn probability_to_accept_neighbor_state(energy_state: f64, energy_neighbor: f64,
     }
 }
 
-fn should_move_to_neighbor(ts: &ThickSurface, energy_state: f64, energy_neighbor: f64, temperature: f64, rng: &mut rand::rngs::ThreadRng) -> bool {
-    let lines1 = graph::graphs_to_lines(&ts.layers);
+fn should_move_to_neighbor(
+    ts: &ThickSurface,
+    energy_state: f64,
+    energy_neighbor: f64,
+    temperature: f64,
+    params: &Params,
+    rng: &mut StdRng,
+) -> bool {
     let coin_flip = rng.gen_range(0.0, 1.0);
-    match lines_intersection(&lines1) {
+    match containment(ts).or_else(|| separation::violation(ts, &params.separation)) {
         Some(_) => false,
         None => {
             if probability_to_accept_neighbor_state(energy_state, energy_neighbor, temperature) < coin_flip {
@@ -91,36 +112,10 @@ fn should_move_to_neighbor(ts: &ThickSurface, energy_state: f64, energy_neighbor
     }
 }
 
-fn add_single_node_effects(ts: &mut ThickSurface, layer_to_add: usize, addition_threshold: f64) {
-    let graph_to_which_add = &ts.layers[layer_to_add];
-
-    for n in &graph_to_which_add.nodes {
-        match graph::node_to_add(graph_to_which_add, n, n.next(&graph_to_which_add), addition_threshold) {
-            Some(addition) => {
-                add_node_(ts, layer_to_add, &addition);
-                break; // THE BREAK IS WHAT LETS THIS WORK, GODDAMN
-            }
-            None => {}
-        }
-    }
-}
-
-fn delete_single_node_effects(ts: &mut ThickSurface, layer_from_which_delete: usize, deletion_threshold: f64, max_merge_steps_away: usize) {
-    let graph_from_which_delete = &ts.layers[layer_from_which_delete];
-    for n in &graph_from_which_delete.nodes {
-        match graph::nodes_to_merge(ts, layer_from_which_delete, n, deletion_threshold, max_merge_steps_away, false) {
-            Some(deletion) => {
-                merge_nodes_(ts, &deletion);
-                break; // THE BREAK IS WHAT LETS THIS WORK, GODDAMN
-            }
-            None => {}
-        }
-    }
-}
-
 pub fn step(sim_state: &mut SimState, params: &Params) {
-    let energy_state = energy(&sim_state.ts, params.initial_gray_matter_area);
-    let neighbor = neighbor(
+    // The separation penalty (if any) counts towards accepting moves, not towards the recorded energy
+    let energy_state = energy(&sim_state.ts, params.initial_gray_matter_area) + separation::penalty(&sim_state.ts, &params.separation);
+    let (neighbor, broken_by) = neighbor(
         &sim_state.ts,
         OUTER,
         INNER,
@@ -128,17 +123,26 @@ pub fn step(sim_state: &mut SimState, params: &Params) {
         params.compression_factor,
         &sim_state.stitching,
         params.low_high,
-        params.node_addition_threshold,
-        params.node_deletion_threshold,
-        params.max_merge_steps_away,
+        params,
         &mut sim_state.rng,
     );
-    let energy_neighbor = energy(&neighbor, params.initial_gray_matter_area);
+    let energy_neighbor = energy(&neighbor, params.initial_gray_matter_area) + separation::penalty(&neighbor, &params.separation);
 
-    if should_move_to_neighbor(&neighbor, energy_state, energy_neighbor, sim_state.temperature, &mut sim_state.rng) {
+    let accepted = should_move_to_neighbor(&neighbor, energy_state, energy_neighbor, sim_state.temperature, params, &mut sim_state.rng);
+    if accepted {
+        // Neighbors that get rejected are allowed to be broken, only the ones we move to matter
+        if let Some((operation, violations)) = broken_by {
+            panic!("{}", report(operation, sim_state.timestep, &violations))
+        }
         sim_state.ts = neighbor;
     };
+    sim_state.last_step = Some(StepOutcome {
+        accepted,
+        energy_before: energy_state,
+        energy_proposed: energy_neighbor,
+    });
 
     sim_state.temperature = temperature(sim_state, params.temperature_param);
     sim_state.timestep += 1;
+    remesh_if_due(sim_state, params);
 }
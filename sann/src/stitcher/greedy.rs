@@ -26,12 +26,12 @@ pub fn stitch(outer: &Graph, inner: &Graph) -> types::Stitching {
         if out_c >= outer.nodes.len() {
             // put_and_walk(&mut inn_c, &mut res, &inn_n, &out_n, &mut inn_n, &inner);
             inn_c += 1;
-            res.put((inn_n.id, inn_n.x, inn_n.y), (out_n.id, out_n.x, out_n.y));
+            res.put((inn_n.handle, inn_n.x, inn_n.y), (out_n.handle, out_n.x, out_n.y));
             inn_n = inn_n.next(&inner);
         } else if inn_c >= inner.nodes.len() {
             //put_and_walk(&mut out_c, &mut res, &inn_n, &out_n, &mut out_n, &outer);
             out_c += 1;
-            res.put((inn_n.id, inn_n.x, inn_n.y), (out_n.id, out_n.x, out_n.y));
+            res.put((inn_n.handle, inn_n.x, inn_n.y), (out_n.handle, out_n.x, out_n.y));
             out_n = out_n.next(&outer);
         } else {
             let dist_crossing_from_out = distance_between_nodes(out_n, inn_n.next(&inner));
@@ -39,12 +39,12 @@ pub fn stitch(outer: &Graph, inner: &Graph) -> types::Stitching {
             if dist_crossing_from_inn < dist_crossing_from_out {
                 //put_and_walk(&mut out_c, &mut res, &inn_n, &out_n, &mut out_n, &outer);
                 out_c += 1;
-                res.put((inn_n.id, inn_n.x, inn_n.y), (out_n.id, out_n.x, out_n.y));
+                res.put((inn_n.handle, inn_n.x, inn_n.y), (out_n.handle, out_n.x, out_n.y));
                 out_n = out_n.next(&outer);
             } else {
                 //put_and_walk(&mut inn_c, &mut res, &inn_n, &out_n, &mut inn_n, &inner);
                 inn_c += 1;
-                res.put((inn_n.id, inn_n.x, inn_n.y), (out_n.id, out_n.x, out_n.y));
+                res.put((inn_n.handle, inn_n.x, inn_n.y), (out_n.handle, out_n.x, out_n.y));
                 inn_n = inn_n.next(&inner);
             }
         }
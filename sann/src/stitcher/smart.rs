@@ -97,8 +97,8 @@ pub fn stitch(outer: &Graph, inner: &Graph) -> types::Stitching {
     let mut ret = Stitching::new();
     for RowCol(row, col) in shortest_path {
         ret.put(
-            (row, inner.nodes[row].x, inner.nodes[row].y),
-            (col, outer.nodes[col].x, outer.nodes[col].y),
+            (inner.nodes[row].handle, inner.nodes[row].x, inner.nodes[row].y),
+            (outer.nodes[col].handle, outer.nodes[col].x, outer.nodes[col].y),
         );
     }
     ret
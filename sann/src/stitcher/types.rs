@@ -1,12 +1,13 @@
 use graph::{distance_between_points};
 
-use graph::types::{Node, INNER, OUTER};
+use graph::types::{Node, NodeHandle, NodeIndex, ThickSurface, INNER, OUTER};
 use std::collections::HashMap;
 use vec1::Vec1;
 
+// Keyed by node handles rather than ids, so correspondences stay attached to their nodes as the layers change
 #[derive(Clone, Debug)]
 pub enum ListMap {
-    LMap(HashMap<usize, Vec1<(usize, f64, f64)>>),
+    LMap(HashMap<NodeHandle, Vec1<(NodeHandle, f64, f64)>>),
 }
 
 impl ListMap {
@@ -14,7 +15,7 @@ impl ListMap {
         ListMap::LMap(HashMap::new())
     }
 
-    pub fn get(&self, key: usize) -> &Vec1<(usize, f64, f64)> {
+    pub fn get(&self, key: NodeHandle) -> &Vec1<(NodeHandle, f64, f64)> {
         match self {
             ListMap::LMap(m) => match m.get(&key) {
                 Some(v) => v,
@@ -23,7 +24,7 @@ impl ListMap {
         }
     }
 
-    pub fn put(&mut self, key: usize, val: (usize, f64, f64)) {
+    pub fn put(&mut self, key: NodeHandle, val: (NodeHandle, f64, f64)) {
         match self {
             ListMap::LMap(m) => match m.get_mut(&key) {
                 Some(v) => {
@@ -47,8 +48,8 @@ impl ListMap {
 }
 
 impl IntoIterator for ListMap {
-    type Item = (usize, vec1::Vec1<(usize, f64, f64)>);
-    type IntoIter = std::collections::hash_map::IntoIter<usize, vec1::Vec1<(usize, f64, f64)>>;
+    type Item = (NodeHandle, vec1::Vec1<(NodeHandle, f64, f64)>);
+    type IntoIter = std::collections::hash_map::IntoIter<NodeHandle, vec1::Vec1<(NodeHandle, f64, f64)>>;
 
     fn into_iter(self) -> Self::IntoIter {
         match self {
@@ -58,8 +59,8 @@ impl IntoIterator for ListMap {
 }
 
 impl<'a> IntoIterator for &'a ListMap {
-    type Item = (&'a usize, &'a vec1::Vec1<(usize, f64, f64)>);
-    type IntoIter = std::collections::hash_map::Iter<'a, usize, vec1::Vec1<(usize, f64, f64)>>;
+    type Item = (&'a NodeHandle, &'a vec1::Vec1<(NodeHandle, f64, f64)>);
+    type IntoIter = std::collections::hash_map::Iter<'a, NodeHandle, vec1::Vec1<(NodeHandle, f64, f64)>>;
 
     fn into_iter(self) -> Self::IntoIter {
         match self {
@@ -77,7 +78,7 @@ impl Stitching {
     pub fn new() -> Stitching {
         Stitching::Stitch(Vec::from([ListMap::new(), ListMap::new()]))
     }
-    pub fn put(&mut self, inn: (usize, f64, f64), out: (usize, f64, f64)) {
+    pub fn put(&mut self, inn: (NodeHandle, f64, f64), out: (NodeHandle, f64, f64)) {
         match self {
             Stitching::Stitch(layers) => {
                 layers[OUTER].put(out.0, inn);
@@ -86,19 +87,19 @@ impl Stitching {
         }
     }
 
-    pub fn get(&self, layer_id: usize, n: &Node) -> Vec1<usize> {
+    pub fn get(&self, layer_id: usize, n: &Node) -> Vec1<NodeHandle> {
         match self {
-            Stitching::Stitch(layers) => match Vec1::try_from_vec(layers[layer_id].get(n.id).iter().map(|(id, _, _)| *id).collect::<Vec<usize>>()) {
+            Stitching::Stitch(layers) => match Vec1::try_from_vec(layers[layer_id].get(n.handle).iter().map(|(h, _, _)| *h).collect::<Vec<NodeHandle>>()) {
                 Ok(s) => s,
-                Err(_) => panic!("VA SE FUDER"),
+                Err(_) => panic!("Node {} of layer {} has no correspondents in the stitching", n.id, layer_id),
             },
         }
     }
 
-    pub fn get_closest_correspondent(&self, layer_id: usize, n: &Node) -> usize {
+    pub fn get_closest_correspondent(&self, layer_id: usize, n: &Node) -> NodeHandle {
         match self {
             Stitching::Stitch(layers) => {
-                let corrs = layers[layer_id].get(n.id);
+                let corrs = layers[layer_id].get(n.handle);
                 corrs
                     .iter()
                     .min_by(|(_, x1, y1), (_, x2, y2)| {
@@ -112,6 +113,33 @@ impl Stitching {
         }
     }
 
+    // Every (outer handle, inner handle) correspondence
+    pub fn pairs(&self) -> Vec<(NodeHandle, NodeHandle)> {
+        match self {
+            Stitching::Stitch(layers) => {
+                let mut ret = Vec::new();
+                for (outer_h, corrs) in &layers[OUTER] {
+                    for (inner_h, _, _) in corrs {
+                        ret.push((*outer_h, *inner_h));
+                    }
+                }
+                ret
+            }
+        }
+    }
+
+    // Every correspondence as (outer index, inner index) in ts as it is now, leaving out nodes that have been merged away
+    pub fn positions(&self, ts: &ThickSurface) -> Vec<(NodeIndex, NodeIndex)> {
+        let (outer, inner) = (ts.layers[OUTER].positions(), ts.layers[INNER].positions());
+        self.pairs()
+            .iter()
+            .filter_map(|(o, i)| match (outer.get(o), inner.get(i)) {
+                (Some(o), Some(i)) => Some((*o, *i)),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         match self {
             Stitching::Stitch(layers) => {
@@ -1,4 +1,16 @@
-#[derive(Clone)]
+use analysis::helmholtz::HeatParams;
+use file_io::animation::AnimationParams;
+use file_io::recorders::Cadence;
+use file_io::svg::SvgParams;
+use file_io::snapshots::SnapshotLayout;
+use file_io::trajectory::TrajectoryParams;
+use graph::pins::PinnedRegion;
+use graph::refine::AdaptiveRefinement;
+use graph::separation::Separation;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+#[derive(Clone, Debug)]
 pub struct Params {
     pub initial_thickness: f64,
     pub initial_radius: f64,
@@ -13,6 +25,77 @@ pub struct Params {
     pub node_deletion_threshold: f64,
     pub low_high: (f64, f64),
     pub recorders: Vec<String>,
+    // When the recorders that don't go every step write their columns, by recorder name
+    pub recorder_cadences: BTreeMap<String, Cadence>,
     pub temperature_param: f64,
     pub output_file_path: String,
+    // Optional: resample both layers every remesh_every steps, to remesh_spacing or to their current node counts
+    pub remesh_every: Option<u64>,
+    pub remesh_spacing: Option<f64>,
+    // Optional: resample the layers to this many nodes before writing them out
+    pub export_num_points: Option<usize>,
+    // How often coord writes a snapshot of the surface, and whether to one file each or all to one file
+    pub snapshot_every: u64,
+    pub snapshot_layout: SnapshotLayout,
+    // Optional: validate the surface after every operation of every step, stopping at the first one that breaks it
+    pub debug_checks: bool,
+    // Optional: seed for the run's random numbers, so a run can be repeated. Without it every run draws its own
+    pub seed: Option<u64>,
+    // Optional [adaptive_refinement] table: thresholds that depend on curvature and on the distance between layers
+    pub adaptive_refinement: Option<AdaptiveRefinement>,
+    // Optional [separation] table: how close edges that aren't neighbors may get, as a hard limit or with a penalty
+    pub separation: Option<Separation>,
+    // Optional [[pinned]] tables: arcs of nodes that never move
+    pub pinned: Vec<PinnedRegion>,
+    // Optional [helmholtz] table: the heat analysis coord runs on the final surface
+    pub heat: Option<HeatParams>,
+    // Optional [svg] table: the figure coord draws of the final surface
+    pub svg: Option<SvgParams>,
+    // Optional [animation] table: a movie of the run
+    pub animation: Option<AnimationParams>,
+    // Optional [trajectory] table: the whole run in one binary file
+    pub trajectory: Option<TrajectoryParams>,
+}
+
+// Gray matter of the initial annulus. Derived from the radius and thickness, never read from the parameters file
+pub fn annulus_area(radius: f64, thickness: f64) -> f64 {
+    PI * (radius.powf(2.0) - (radius - thickness).powf(2.0))
+}
+
+impl Default for Params {
+    // Same values as the parameters.toml at the root of the crate, except that nothing gets recorded
+    fn default() -> Params {
+        Params {
+            initial_thickness: 0.1,
+            initial_radius: 1.0,
+            initial_num_points: 5000,
+            initial_temperature: 10.0,
+            initial_gray_matter_area: annulus_area(1.0, 0.1),
+            compression_factor: 1.1,
+            softness_factor: 10.0,
+            how_smooth: 8,
+            max_merge_steps_away: 3,
+            node_addition_threshold: 1.0,
+            node_deletion_threshold: 0.0,
+            low_high: (-0.2, 0.2),
+            recorders: vec![],
+            recorder_cadences: BTreeMap::new(),
+            temperature_param: -0.05,
+            output_file_path: String::from("output.csv"),
+            remesh_every: None,
+            remesh_spacing: None,
+            export_num_points: None,
+            snapshot_every: 1000,
+            snapshot_layout: SnapshotLayout::Appended,
+            debug_checks: false,
+            seed: None,
+            adaptive_refinement: None,
+            separation: None,
+            pinned: vec![],
+            heat: None,
+            svg: None,
+            animation: None,
+            trajectory: None,
+        }
+    }
 }